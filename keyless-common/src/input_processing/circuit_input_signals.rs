@@ -6,6 +6,7 @@ use crate::logging;
 use anyhow::{anyhow, bail, Result};
 use ark_bn254::Fr;
 use maplit2::hashmap;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{collections::BTreeMap, marker::PhantomData};
 
@@ -23,6 +24,21 @@ pub struct Unpadded;
 #[derive(Debug)]
 pub struct Padded;
 
+/// Length of a variable-length signal, next to the max length that the circuit supports for it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SignalLength {
+    pub signal: String,
+    pub len: usize,
+    /// `None` if the circuit config sets no max length for this signal.
+    pub max_len: Option<usize>,
+}
+
+impl SignalLength {
+    pub fn fits(&self) -> bool {
+        !matches!(self.max_len, Some(max_len) if self.len > max_len)
+    }
+}
+
 #[derive(Debug)]
 pub struct CircuitInputSignals<T> {
     signals: BTreeMap<String, CircuitInputSignal>,
@@ -143,6 +159,25 @@ impl CircuitInputSignals<Unpadded> {
         })
     }
 
+    /// Lengths of all the signals that `pad` will pad, i.e., all bytes and limbs signals.
+    pub fn lengths(&self, config: &CircuitConfig) -> Vec<SignalLength> {
+        self.signals
+            .iter()
+            .filter_map(|(k, v)| {
+                let len = match v {
+                    CircuitInputSignal::Limbs(x) => x.len(),
+                    CircuitInputSignal::Bytes(b) => b.len(),
+                    _ => return None,
+                };
+                Some(SignalLength {
+                    signal: k.clone(),
+                    len,
+                    max_len: config.max_lengths.get(k).copied(),
+                })
+            })
+            .collect()
+    }
+
     pub fn pad(self, config: &CircuitConfig) -> Result<CircuitInputSignals<Padded>> {
         let padded_signals_vec: Result<Vec<(String, CircuitInputSignal)>> = self
             .signals
@@ -10,6 +10,7 @@ use ark_bn254::Fr;
 use ark_ff::{BigInteger, PrimeField};
 use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 pub type RsaSignature = BigUint;
 
@@ -106,9 +107,23 @@ pub struct JwtParts {
     signature: String,
 }
 
+/// The header of a JWT, or of a JWE if `enc` is set. Only `kid` and `alg` are used to verify the
+/// signature: the key is always looked up by `kid` among the issuer's JWKs, never taken from `jku`
+/// or `x5c`. Missing `kid` and `alg` deserialize as empty, so that callers can reject them with a
+/// precise error.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JwtHeader {
+    #[serde(default)]
     pub kid: String,
+    #[serde(default)]
+    pub alg: String,
+    pub typ: Option<String>,
+    pub jku: Option<String>,
+    pub x5c: Option<Vec<String>>,
+    /// Header params that the recipient must understand to process the JWT.
+    pub crit: Option<Vec<String>>,
+    /// The content encryption algorithm, which only JWEs have.
+    pub enc: Option<String>,
 }
 
 impl JwtHeader {
@@ -128,7 +143,41 @@ pub struct JwtPayload {
     pub sub: Option<String>,
     pub email: Option<String>,
     pub email_verified: Option<bool>,
-    pub aud: String,
+    pub aud: JwtAud,
+}
+
+/// A JWT's `aud`: a single audience, or an array of them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum JwtAud {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl JwtAud {
+    pub fn contains(&self, aud: &str) -> bool {
+        match self {
+            Self::Single(single) => single == aud,
+            Self::Multiple(multiple) => multiple.iter().any(|value| value == aud),
+        }
+    }
+
+    /// The audience, if `aud` is a single string: the circuit cannot parse arrays.
+    pub fn as_single(&self) -> Option<&str> {
+        match self {
+            Self::Single(single) => Some(single),
+            Self::Multiple(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for JwtAud {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Single(single) => write!(f, "{single}"),
+            Self::Multiple(multiple) => write!(f, "[{}]", multiple.join(", ")),
+        }
+    }
 }
 
 impl JwtPayload {
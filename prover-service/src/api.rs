@@ -1,13 +1,20 @@
 // Copyright © Aptos Foundation
 
+use crate::config::{IssuerPolicy, TwSigningMode};
+use crate::groth16_vk::OnChainGroth16VerificationKey;
+use crate::prover_key::OnChainKeylessConfiguration;
+use aptos_crypto::HashValue;
 use aptos_crypto_derive::BCSCryptoHash;
 use aptos_crypto_derive::CryptoHasher;
-use aptos_keyless_common::PoseidonHash;
+use aptos_keyless_common::{input_processing::circuit_input_signals::SignalLength, PoseidonHash};
 use aptos_types::{
     keyless::{Groth16Proof, Pepper},
     transaction::authenticator::EphemeralPublicKey,
 };
+use axum::http::StatusCode;
+use jsonwebtoken::Algorithm;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 //#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 //pub struct EphemeralPublicKeyBlinder(pub(crate) Vec<u8>);
@@ -30,9 +37,13 @@ pub struct RequestInput {
     pub use_insecure_test_jwk: bool,
     #[serde(default)]
     pub skip_aud_checks: bool,
+    /// Which of the circuit setups listed in `/meta` to prove with, by name or by VK hash. If
+    /// unset, the default setup.
+    #[serde(default)]
+    pub setup: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
 #[allow(clippy::large_enum_variant)] // EphemeralSignature has the WebAuthn (Passkey) variant which is large.
 pub enum ProverServiceResponse {
@@ -44,6 +55,223 @@ pub enum ProverServiceResponse {
         training_wheels_signature: Vec<u8>,
     },
     Error {
+        /// Stable, machine-readable reason for the failure. See [`ErrorCode`].
+        #[serde(default)]
+        error_code: ErrorCode,
+        /// Human-readable description of the failure. Its wording may change at any time, so
+        /// clients should branch on `error_code` instead.
         message: String,
     },
 }
+
+/// Body of `POST /v1/jobs`: a regular prove request, plus where to report the result.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateJobRequest {
+    #[serde(flatten)]
+    pub request: RequestInput,
+    /// If set, a [`JobResponse`] is POSTed to this `http(s)` URL once the job has finished.
+    #[serde(default)]
+    pub callback_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+/// Returned by `POST /v1/jobs` and `GET /v1/jobs/{id}`, and sent to the job's callback URL.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobResponse {
+    pub job_id: String,
+    pub status: JobStatus,
+    /// Set once the job is `done` or `failed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<ProverServiceResponse>,
+}
+
+/// Response of `/v0/validate`: what a `/v0/prove` request with the same body would commit to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidationResponse {
+    #[serde(with = "hex")]
+    pub public_inputs_hash: PoseidonHash,
+    /// The JWT's `nonce`, which was checked against the EPK, its expiry date and blinder.
+    pub nonce: String,
+    /// Lengths of the variable-length circuit inputs, sorted by signal name, next to the max
+    /// lengths that the circuit supports for them.
+    pub field_lengths: Vec<SignalLength>,
+}
+
+/// A circuit setup that this instance can prove with, as listed in `/meta`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetupInfo {
+    /// Selects this setup as a request's `setup`.
+    pub name: String,
+    /// Selects this setup as a request's `setup`, too. See [`OnChainGroth16VerificationKey::hash`].
+    pub vk_hash: HashValue,
+    /// Whether requests without a `setup` are proved with this setup.
+    pub is_default: bool,
+    pub groth16_vk: OnChainGroth16VerificationKey,
+    /// Max lengths of the circuit's variable-length inputs, by signal name.
+    pub max_lengths: BTreeMap<String, usize>,
+}
+
+/// Body of `/meta`: what clients need in order to build requests for this instance and to use its
+/// proofs on chain. Internal settings, such as paths and debug flags, are left out.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceMetadata {
+    /// The commit the service was built from, if known.
+    pub git_commit: Option<String>,
+    pub setups: Vec<SetupInfo>,
+    pub training_wheels: TrainingWheelsInfo,
+    /// The values that a request's `uid_key` can take, for issuers that are not in `issuers`.
+    pub default_uid_keys: Vec<String>,
+    /// Issuers whose JWKs this instance fetches. With `federated_jwks`, JWTs from issuers that
+    /// match `federated_issuer_patterns` are accepted too.
+    pub issuers: Vec<IssuerInfo>,
+    /// Regexes that match whole `iss` values.
+    pub federated_issuer_patterns: Vec<String>,
+    pub features: ServiceFeatures,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssuerInfo {
+    pub iss: String,
+    /// The checks that requests for this issuer must pass, e.g., the values that their `uid_key`
+    /// can take.
+    #[serde(flatten)]
+    pub policy: IssuerPolicy,
+}
+
+/// The training wheels key that responses are signed with.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrainingWheelsInfo {
+    pub key_id: String,
+    pub pubkey: String,
+    /// The on-chain keyless configuration that lists `pubkey` as the training wheels key.
+    pub on_chain_keyless_configuration: OnChainKeylessConfiguration,
+    /// What the signature covers.
+    pub signing_mode: TwSigningMode,
+}
+
+/// Behavior that clients may need to adapt to.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceFeatures {
+    pub federated_jwks: bool,
+    /// Whether a JWT's `iat` must not be in the future.
+    pub jwt_iat_not_in_future_check: bool,
+    /// Whether a JWT's `exp` must not be in the past.
+    pub jwt_exp_not_in_the_past_check: bool,
+    /// The JWT `alg`s that are accepted.
+    pub jwt_algorithms: Vec<Algorithm>,
+    /// Whether the prove routes need an API key.
+    pub api_key_required: bool,
+    pub max_prove_batch_size: usize,
+}
+
+/// Machine-readable reason for a failed request, returned as the `error_code` field of
+/// [`ProverServiceResponse::Error`] and serialized in `SCREAMING_SNAKE_CASE`
+/// (e.g. `"NONCE_MISMATCH"`).
+///
+/// These codes are part of the API: existing codes are never renamed or repurposed, although new
+/// ones may be added. Clients should treat codes they do not recognize like `INTERNAL_ERROR`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The request body is malformed or otherwise invalid. Do not retry.
+    InvalidRequest,
+    /// The JWT could not be decoded. Do not retry.
+    JwtMalformed,
+    /// The JWT's header rules it out: it is a JWE or unsigned, it has no `kid`, its `typ` is not
+    /// `JWT`, it has `crit` params, or its `alg` is not the one of its JWK. Do not retry.
+    JwtHeaderUnsupported,
+    /// No JWK is known for the JWT's `iss` and `kid`. May succeed later if the issuer just rotated
+    /// its keys.
+    JwkNotFound,
+    /// The JWT's signature does not verify under its JWK. Do not retry.
+    JwtSignatureInvalid,
+    /// The JWT's `alg` is not in `jwt_algorithms` in `/meta`, or the circuit cannot prove its
+    /// signature: only RS256 under keys with public exponent 65537 is supported. Do not retry.
+    JwtAlgorithmUnsupported,
+    /// The JWT's `exp` is in the past. Get a fresh JWT.
+    JwtExpired,
+    /// The JWT's `iat` is in the future. May succeed later if this is due to clock skew.
+    JwtIssuedInFuture,
+    /// The EPK expiry date is beyond `iat + exp_horizon_secs`. Do not retry.
+    EpkExpired,
+    /// The JWT's `nonce` does not commit to the EPK, its expiry date and blinder. Do not retry.
+    NonceMismatch,
+    /// The requested `uid_key` is not supported. Do not retry.
+    UidKeyUnsupported,
+    /// The JWT lacks the claim named by `uid_key`. Do not retry.
+    UidClaimMissing,
+    /// `uid_key` is `email` but the JWT's `email_verified` is not `true`. Do not retry.
+    EmailNotVerified,
+    /// The request is not allowed by its issuer's policy in `/meta`, e.g., the JWT's `aud` is not
+    /// allowed, or `exp_horizon_secs` is too large. Do not retry.
+    IssuerPolicyViolation,
+    /// The JWT's `aud` is an array, which the circuit cannot prove. Do not retry.
+    JwtAudUnsupported,
+    /// A JWT field is longer than the circuit supports. Do not retry.
+    JwtFieldTooLong,
+    /// The request's `setup` names none of the setups listed in `/meta`. Do not retry.
+    SetupNotFound,
+    /// The prover is at capacity. Retry after the delay in the `Retry-After` header.
+    ProverBusy,
+    /// This instance is shutting down. Retry, and the request will go to another instance.
+    ShuttingDown,
+    /// The route needs an API key or a TLS client certificate, and the request had none or an
+    /// unknown one.
+    Unauthorized,
+    /// The client, issuer or user sent too many requests. Retry after the delay in the
+    /// `Retry-After` header.
+    RateLimited,
+    /// Witness generation failed. May succeed on retry.
+    WitnessGenFailed,
+    /// The Groth16 prover failed. May succeed on retry.
+    ProverFailed,
+    /// Signing the proof with the training wheels key failed, e.g., because the remote signer is
+    /// unreachable. May succeed on retry.
+    SigningFailed,
+    /// No job with the requested id exists, or it expired. Do not retry.
+    JobNotFound,
+    /// Any other server-side failure. May succeed on retry.
+    #[default]
+    InternalError,
+}
+
+impl ErrorCode {
+    /// HTTP status to respond with, unless the handler chose one explicitly.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest
+            | ErrorCode::JwtMalformed
+            | ErrorCode::JwtHeaderUnsupported
+            | ErrorCode::JwkNotFound
+            | ErrorCode::JwtSignatureInvalid
+            | ErrorCode::JwtAlgorithmUnsupported
+            | ErrorCode::JwtExpired
+            | ErrorCode::JwtIssuedInFuture
+            | ErrorCode::EpkExpired
+            | ErrorCode::NonceMismatch
+            | ErrorCode::UidKeyUnsupported
+            | ErrorCode::UidClaimMissing
+            | ErrorCode::EmailNotVerified
+            | ErrorCode::IssuerPolicyViolation
+            | ErrorCode::JwtAudUnsupported
+            | ErrorCode::JwtFieldTooLong
+            | ErrorCode::SetupNotFound => StatusCode::BAD_REQUEST,
+            ErrorCode::JobNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::ProverBusy | ErrorCode::ShuttingDown => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::WitnessGenFailed
+            | ErrorCode::ProverFailed
+            | ErrorCode::SigningFailed
+            | ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    api::ErrorCode,
+    config::{ApiKey, ProverServiceConfig},
+    error::{self, CodedError, ErrorWithCode},
+    metrics,
+    rate_limit::RateLimiter,
+    state::ProverServiceState,
+    tls::ClientCertificate,
+};
+use aptos_crypto::HashValue;
+use axum::{
+    extract::{Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{collections::HashMap, sync::Arc};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+struct AuthorizedKey {
+    name: String,
+    quota: Option<RateLimiter>,
+}
+
+/// The API keys configured in `ProverServiceConfig`. Keys are only kept as hashes, so that
+/// looking one up does not compare secrets byte by byte.
+#[derive(Default)]
+pub struct ApiKeyAuth {
+    keys: HashMap<HashValue, AuthorizedKey>,
+}
+
+impl ApiKeyAuth {
+    pub fn from_config(api_keys: &[ApiKey]) -> Self {
+        Self {
+            keys: api_keys
+                .iter()
+                .map(|api_key| {
+                    (
+                        HashValue::sha3_256_of(api_key.key.as_bytes()),
+                        AuthorizedKey {
+                            name: api_key.name.clone(),
+                            quota: api_key.quota.map(RateLimiter::new),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether requests need an API key at all.
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Check the request's API key and take one request from its quota. Returns the key's name.
+    pub fn authorize(&self, headers: &HeaderMap) -> Result<&str, ErrorWithCode> {
+        let presented_key = headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                CodedError::msg(
+                    ErrorCode::Unauthorized,
+                    format!("Missing {API_KEY_HEADER} header"),
+                )
+            })?;
+        let key = self
+            .keys
+            .get(&HashValue::sha3_256_of(presented_key.as_bytes()))
+            .ok_or_else(|| CodedError::msg(ErrorCode::Unauthorized, "Unknown API key"))?;
+
+        if let Some(quota) = &key.quota {
+            quota.check(&key.name).map_err(|retry_after| {
+                metrics::RATE_LIMITED_REQUESTS
+                    .with_label_values(&["api_key"])
+                    .inc();
+                error::rate_limited("API key", retry_after)
+            })?;
+        }
+        Ok(&key.name)
+    }
+}
+
+/// Middleware that rejects requests without a known API key, if any API keys are configured.
+pub async fn require_api_key(
+    State(state): State<Arc<ProverServiceState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.api_keys.is_enabled() {
+        return next.run(request).await;
+    }
+    match state.api_keys.authorize(request.headers()) {
+        Ok(name) => {
+            metrics::API_KEY_REQUESTS.with_label_values(&[name]).inc();
+            next.run(request).await
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Whether `require_client_certificate` rejects clients without a verified TLS client certificate.
+/// Taken from the config once at startup, like the TLS acceptor that verifies the certificates, so
+/// that a config reload cannot turn the check on or off.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientCertificatePolicy {
+    pub required: bool,
+}
+
+impl ClientCertificatePolicy {
+    pub fn from_config(config: &ProverServiceConfig) -> Self {
+        Self {
+            required: config
+                .tls
+                .as_ref()
+                .is_some_and(|tls| tls.client_ca_path.is_some()),
+        }
+    }
+}
+
+/// Middleware that rejects requests from clients without a verified TLS client certificate, if
+/// `tls.client_ca_path` was configured at startup.
+pub async fn require_client_certificate(
+    State(policy): State<ClientCertificatePolicy>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let verified = request
+        .extensions()
+        .get::<ClientCertificate>()
+        .is_some_and(|client_certificate| client_certificate.verified);
+    if policy.required && !verified {
+        return ErrorWithCode::from(CodedError::msg(
+            ErrorCode::Unauthorized,
+            "This route needs a TLS client certificate",
+        ))
+        .into_response();
+    }
+    next.run(request).await
+}
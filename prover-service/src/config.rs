@@ -1,12 +1,15 @@
 // Copyright © Aptos Foundation
 
 use crate::groth16_vk::{OnChainGroth16VerificationKey, SnarkJsGroth16VerificationKey};
+use anyhow::{Context, Result};
 use aptos_keyless_common::input_processing::config::CircuitConfig;
 use figment::providers::{Env, Format, Yaml};
 use figment::Figment;
+use jsonwebtoken::Algorithm;
 use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{collections::BTreeMap, fs, net::IpAddr};
 
 pub const CONFIG_FILE_PATH: &str = "config.yml";
 pub const LOCAL_TESTING_CONFIG_FILE_PATH: &str = "config_local_testing.yml";
@@ -16,21 +19,248 @@ fn default_true() -> bool {
     true
 }
 
+/// The JWT claims that requests may use as `uid_key`, unless their issuer's `uid_keys` say
+/// otherwise.
+pub const DEFAULT_UID_KEYS: [&str; 2] = ["sub", "email"];
+
+fn default_uid_keys() -> Vec<String> {
+    DEFAULT_UID_KEYS.map(String::from).to_vec()
+}
+
+fn default_allowed_jwt_algorithms() -> Vec<Algorithm> {
+    vec![Algorithm::RS256]
+}
+
+/// Same as `jsonwebtoken`'s default `exp` leeway.
+fn default_exp_leeway_secs() -> u64 {
+    60
+}
+
+fn default_active_tw_key_id() -> String {
+    String::from("0")
+}
+
+fn default_remote_signer_timeout_secs() -> u64 {
+    5
+}
+
+/// Auth0 and Cognito tenants.
+fn default_federated_issuer_patterns() -> Vec<IssuerPattern> {
+    [
+        r"https://[a-zA-Z0-9-_]+\.us\.auth0\.com/",
+        r"https://cognito-idp\.[a-zA-Z0-9-_]+\.amazonaws\.com/[a-zA-Z0-9-_]+",
+    ]
+    .into_iter()
+    .map(|pattern| IssuerPattern::try_from(String::from(pattern)).unwrap())
+    .collect()
+}
+
+fn default_jwk_fetch_timeout_secs() -> u64 {
+    5
+}
+
+fn default_jwk_max_refresh_interval_secs() -> u64 {
+    3600
+}
+
+fn default_jwk_max_backoff_secs() -> u64 {
+    300
+}
+
+fn default_jwk_grace_period_secs() -> u64 {
+    300
+}
+
+fn default_num_prover_workers() -> usize {
+    1
+}
+
+fn default_max_prover_queue_depth() -> usize {
+    64
+}
+
+fn default_max_prover_queue_wait_secs() -> u64 {
+    60
+}
+
+fn default_prover_queue_retry_after_secs() -> u64 {
+    5
+}
+
+fn default_prover_liveness_deadline_secs() -> u64 {
+    300
+}
+
+fn default_max_prove_batch_size() -> usize {
+    16
+}
+
+fn default_job_ttl_secs() -> u64 {
+    600
+}
+
+fn default_job_callback_timeout_secs() -> u64 {
+    10
+}
+
+fn default_shutdown_drain_timeout_secs() -> u64 {
+    120
+}
+
+fn default_admin_port() -> u16 {
+    9101
+}
+
+fn default_setup_self_test_witness_filename() -> String {
+    String::from("self_test.wtns")
+}
+
+fn default_tls_reload_poll_secs() -> u64 {
+    10
+}
+
+fn default_enable_proof_cache() -> bool {
+    true
+}
+
+fn default_proof_cache_capacity() -> usize {
+    1024
+}
+
+fn default_proof_cache_max_ttl_secs() -> u64 {
+    600
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 //#[serde(deny_unknown_fields)]
 pub struct ProverServiceConfig {
     pub git_commit: Option<String>,
     pub setup_dir: String,
+    /// More setup dirs in `resources_dir` to serve next to `setup_dir`, e.g., the next VK while
+    /// its on-chain upgrade is pending. Requests select one of them by name or VK hash; those that
+    /// do not are proved with `setup_dir`.
+    #[serde(default)]
+    pub additional_setup_dirs: Vec<String>,
     /// Directory with prover/verification key and witness gen binary
     pub resources_dir: String,
     pub zkey_filename: String,
     pub test_verification_key_filename: String,
     pub witness_gen_binary_filename: String,
+    /// A witness for the circuit, in the setup dir. Before a reloaded setup replaces the current
+    /// one, it must prove this witness with the new prover key and verify under the new VK.
+    #[serde(default = "default_setup_self_test_witness_filename")]
+    pub setup_self_test_witness_filename: String,
+    /// Circuit input that the self-test witness is generated from, with the reloaded setup's own
+    /// witness generator, if the setup dir has no `setup_self_test_witness_filename`. Only works
+    /// for circuits with the same inputs and max lengths as the one the input was made for.
+    #[serde(default)]
+    pub setup_self_test_input_path: Option<String>,
+    /// Number of rapidsnark instances to run proofs on concurrently. Each one keeps its own copy of
+    /// the prover key in memory.
+    #[serde(default = "default_num_prover_workers")]
+    pub num_prover_workers: usize,
+    /// Max number of requests that may be waiting for a prover worker. Further requests are
+    /// rejected with a 503.
+    #[serde(default = "default_max_prover_queue_depth")]
+    pub max_prover_queue_depth: usize,
+    /// Max time a request may wait for a prover worker, counted from its arrival.
+    #[serde(default = "default_max_prover_queue_wait_secs")]
+    pub max_prover_queue_wait_secs: u64,
+    /// Value of the `Retry-After` header sent along with a 503 when the queue rejects a request.
+    #[serde(default = "default_prover_queue_retry_after_secs")]
+    pub prover_queue_retry_after_secs: u64,
+    /// `/livez` fails once a single proof has held a prover worker for longer than this.
+    #[serde(default = "default_prover_liveness_deadline_secs")]
+    pub prover_liveness_deadline_secs: u64,
+    /// On SIGTERM or SIGINT, how long to wait for in-flight proofs to finish before exiting.
+    #[serde(default = "default_shutdown_drain_timeout_secs")]
+    pub shutdown_drain_timeout_secs: u64,
+    /// Max number of requests in a single `/v0/prove_batch` call.
+    #[serde(default = "default_max_prove_batch_size")]
+    pub max_prove_batch_size: usize,
+    /// How long a finished `/v1/jobs` job, and its result, is kept around for polling.
+    #[serde(default = "default_job_ttl_secs")]
+    pub job_ttl_secs: u64,
+    /// Timeout for notifying a job's callback URL.
+    #[serde(default = "default_job_callback_timeout_secs")]
+    pub job_callback_timeout_secs: u64,
+    /// Hosts that jobs' callback URLs may point to. If empty, callback URLs may point to any
+    /// host that has a public IP address, but not to loopback, private or link-local ones.
+    #[serde(default)]
+    pub job_callback_allowed_hosts: Vec<String>,
+    /// Whether to answer repeats of an already-proven request from the proof cache.
+    #[serde(default = "default_enable_proof_cache")]
+    pub enable_proof_cache: bool,
+    /// Max number of responses in the proof cache.
+    #[serde(default = "default_proof_cache_capacity")]
+    pub proof_cache_capacity: usize,
+    /// Max time a response stays in the proof cache. Entries are evicted sooner if the request's
+    /// EPK expires sooner.
+    #[serde(default = "default_proof_cache_max_ttl_secs")]
+    pub proof_cache_max_ttl_secs: u64,
+    /// Rate limit for prove requests from a single client IP. Unlimited if unset.
+    #[serde(default)]
+    pub rate_limit_per_client_ip: Option<RateLimit>,
+    /// Proxies in front of the service, e.g., its load balancer. For requests they forward, the
+    /// client IP that `rate_limit_per_client_ip` applies to is the rightmost address in the
+    /// `X-Forwarded-For` header that is not one of them.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpRange>,
+    /// Rate limit for prove requests for JWTs from a single issuer. Unlimited if unset.
+    #[serde(default)]
+    pub rate_limit_per_iss: Option<RateLimit>,
+    /// Rate limit for prove requests for a single user, i.e., a single `iss` and uid value.
+    /// Unlimited if unset. Every request in a batch counts, so `burst` should be at least
+    /// `max_prove_batch_size`.
+    #[serde(default)]
+    pub rate_limit_per_uid: Option<RateLimit>,
+    /// API keys that may call the prove and validate routes, sent in the `X-API-Key` header. If
+    /// empty, these routes need no API key.
+    #[serde(default)]
+    pub api_keys: Vec<ApiKey>,
+    /// If set, serve the public routes over TLS instead of plain HTTP.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// If set, check the setup dir's prover key, VK, circuit config and witness gen binary for
+    /// changes this often, and reload the setup when they change. Reloads can also be triggered
+    /// with `POST /admin/reload_setup` on the admin port.
+    #[serde(default)]
+    pub setup_reload_poll_secs: Option<u64>,
+    /// Id of the training wheels key to sign with. Keys are read from the `PRIVATE_KEY_<id>`
+    /// environment variables (e.g., `PRIVATE_KEY_0`), from `tw_keys_file` and from `tw_signers`.
+    #[serde(default = "default_active_tw_key_id")]
+    pub active_tw_key_id: String,
+    /// YAML file that maps training wheels key ids to private keys. Unlike the environment, it is
+    /// read again on every config reload, so keys can be rotated without a restart.
+    #[serde(default)]
+    pub tw_keys_file: Option<String>,
+    /// Training wheels keys that are kept out of the environment, by key id.
+    #[serde(default)]
+    pub tw_signers: BTreeMap<String, TwSignerConfig>,
+    /// What the training wheels signature covers. Must match what the chain expects.
+    #[serde(default)]
+    pub training_wheels_signing_mode: TwSigningMode,
 
     pub oidc_providers: Vec<OidcProvider>,
+    /// How often to fetch each provider's JWKs, at least. A longer `max-age` in the response's
+    /// `Cache-Control` is honored, up to `jwk_max_refresh_interval_secs`.
     pub jwk_refresh_rate_secs: u64,
+    #[serde(default = "default_jwk_max_refresh_interval_secs")]
+    pub jwk_max_refresh_interval_secs: u64,
+    /// After failed fetches, the retry delay doubles from `jwk_refresh_rate_secs` up to this.
+    #[serde(default = "default_jwk_max_backoff_secs")]
+    pub jwk_max_backoff_secs: u64,
+    /// How long to keep accepting a JWK after its provider stopped serving it. This lets JWTs that
+    /// were signed just before a key rotation still be proved, but it also keeps a key that the
+    /// provider withdrew because it leaked usable for that long, so keep it short.
+    #[serde(default = "default_jwk_grace_period_secs")]
+    pub jwk_grace_period_secs: u64,
     pub port: u16,
     pub metrics_port: u16,
+    /// Port of the admin routes, e.g., `POST /admin/reload_setup`. They are unauthenticated, so
+    /// they are only served on localhost.
+    #[serde(default = "default_admin_port")]
+    pub admin_port: u16,
     // Whether to log sensitive data
     pub enable_dangerous_logging: bool,
     pub enable_debug_checks: bool,
@@ -38,29 +268,78 @@ pub struct ProverServiceConfig {
     pub enable_test_provider: bool,
     #[serde(default)]
     pub enable_federated_jwks: bool,
+    /// Regexes for the issuers whose JWKs are fetched on demand, through OIDC discovery, if
+    /// `enable_federated_jwks` is set. A pattern must match the whole `iss`. The discovered JWK
+    /// endpoint must be on the issuer's own HTTPS origin, or match one of these patterns too.
+    #[serde(default = "default_federated_issuer_patterns")]
+    pub federated_issuer_patterns: Vec<IssuerPattern>,
+    /// The checks for issuers that match `federated_issuer_patterns`, which have no entry in
+    /// `oidc_providers` to set them in. Takes the same fields as an `oidc_providers` entry's
+    /// checks, with the same defaults.
+    #[serde(default)]
+    pub federated_issuer_policy: IssuerPolicy,
     #[serde(default = "default_true")]
     pub enable_jwt_iat_not_in_future_check: bool,
     #[serde(default = "default_true")]
     pub enable_jwt_exp_not_in_the_past_check: bool,
+    /// The JWT `alg`s that are accepted. Only `RS256` can be proved: JWTs with other allowed
+    /// `alg`s have their signature checked, then fail with `JwtAlgorithmUnsupported`.
+    #[serde(default = "default_allowed_jwt_algorithms")]
+    pub allowed_jwt_algorithms: Vec<Algorithm>,
     #[serde(default)]
     pub use_insecure_jwk_for_test: bool,
 }
 
-pub static CONFIG: Lazy<ProverServiceConfig> = Lazy::new(|| {
+/// The config at startup. Use `ProverServiceState::config()` for the current one, which may have
+/// been reloaded since.
+pub static CONFIG: Lazy<ProverServiceConfig> = Lazy::new(|| load_config().unwrap());
+
+/// Read the config file named by `CONFIG_FILE` (or `config.yml`), with environment variables
+/// overriding its fields.
+pub fn load_config() -> Result<ProverServiceConfig> {
     let config_file_path =
         std::env::var(CONFIG_FILE_PATH_ENVVAR).unwrap_or(String::from(CONFIG_FILE_PATH));
     Figment::new()
-        .merge(Yaml::file(config_file_path))
+        .merge(Yaml::file(&config_file_path))
         .merge(Env::raw())
         .extract()
-        .unwrap()
-});
+        .with_context(|| format!("failed to load config from {config_file_path}"))
+}
 
 impl ProverServiceConfig {
     pub fn setup_dir(&self) -> &String {
         &self.setup_dir
     }
 
+    /// The checks that requests for `iss` must pass: those of its provider, or
+    /// `federated_issuer_policy` if it has none, because its JWKs are federated.
+    pub fn issuer_policy(&self, iss: &str) -> IssuerPolicy {
+        self.oidc_providers
+            .iter()
+            .find(|provider| provider.iss == iss)
+            .map_or_else(
+                || self.federated_issuer_policy.clone(),
+                OidcProvider::policy,
+            )
+    }
+
+    /// `setup_dir`, followed by `additional_setup_dirs`.
+    pub fn setup_dirs(&self) -> Vec<&str> {
+        std::iter::once(&self.setup_dir)
+            .chain(&self.additional_setup_dirs)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// This config, but for the setup in `setup_dir`: the paths of the result point to that
+    /// setup's files.
+    pub fn for_setup_dir(&self, setup_dir: &str) -> ProverServiceConfig {
+        ProverServiceConfig {
+            setup_dir: String::from(setup_dir),
+            ..self.clone()
+        }
+    }
+
     pub fn zkey_path(&self) -> String {
         shellexpand::tilde(
             &(String::from(&self.resources_dir)
@@ -108,6 +387,23 @@ impl ProverServiceConfig {
         .into_owned()
     }
 
+    pub fn setup_self_test_witness_path(&self) -> String {
+        shellexpand::tilde(
+            &(String::from(&self.resources_dir)
+                + "/"
+                + self.setup_dir()
+                + "/"
+                + &self.setup_self_test_witness_filename),
+        )
+        .into_owned()
+    }
+
+    pub fn setup_self_test_input_path(&self) -> Option<String> {
+        self.setup_self_test_input_path
+            .as_ref()
+            .map(|path| shellexpand::tilde(path).into_owned())
+    }
+
     pub fn circuit_config_path(&self) -> String {
         shellexpand::tilde(
             &(String::from(&self.resources_dir) + "/" + self.setup_dir() + "/circuit_config.yml"),
@@ -115,24 +411,272 @@ impl ProverServiceConfig {
         .into_owned()
     }
 
-    pub fn load_circuit_params(&self) -> CircuitConfig {
+    pub fn load_circuit_params(&self) -> Result<CircuitConfig> {
         let path = self.circuit_config_path();
-        let circuit_config_yaml = std::fs::read_to_string(path).unwrap();
-        serde_yaml::from_str(&circuit_config_yaml).unwrap()
+        let circuit_config_yaml =
+            fs::read_to_string(&path).with_context(|| format!("failed to read {path}"))?;
+        serde_yaml::from_str(&circuit_config_yaml)
+            .with_context(|| format!("failed to parse {path}"))
     }
 
-    pub fn load_vk(&self) -> OnChainGroth16VerificationKey {
+    pub fn load_vk(&self) -> Result<OnChainGroth16VerificationKey> {
         let path = self.verification_key_path();
-        let vk_json = fs::read_to_string(path).unwrap();
-        let local_vk: SnarkJsGroth16VerificationKey =
-            serde_json::from_str(vk_json.as_str()).unwrap();
-        local_vk.try_as_onchain_repr().unwrap()
+        let vk_json =
+            fs::read_to_string(&path).with_context(|| format!("failed to read {path}"))?;
+        let local_vk: SnarkJsGroth16VerificationKey = serde_json::from_str(vk_json.as_str())
+            .with_context(|| format!("failed to parse {path}"))?;
+        local_vk.try_as_onchain_repr()
+    }
+}
+
+/// Token bucket parameters: a client may send `burst` requests at once, and then one every
+/// `60 / requests_per_minute` seconds.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub requests_per_minute: f64,
+    pub burst: u32,
+}
+
+/// An IP address, or a range of them in CIDR notation, e.g., `10.0.0.0/8`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len));
+                let mask = mask.unwrap_or_default();
+                u32::from(addr) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len));
+                let mask = mask.unwrap_or_default();
+                u128::from(addr) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = anyhow::Error;
+
+    fn try_from(range: String) -> Result<Self> {
+        let (addr, prefix_len) = match range.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (range.as_str(), None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .with_context(|| format!("invalid IP range {range}"))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .with_context(|| format!("invalid prefix length in IP range {range}"))?,
+            None => max_prefix_len,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl From<IpRange> for String {
+    fn from(range: IpRange) -> Self {
+        format!("{}/{}", range.addr, range.prefix_len)
+    }
+}
+
+/// A regex that must match the whole of a URL, e.g., an `iss`. It is compiled when the config is
+/// loaded, so that a config with an invalid pattern fails to load.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct IssuerPattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl IssuerPattern {
+    pub fn is_match(&self, url: &str) -> bool {
+        self.regex.is_match(url)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl TryFrom<String> for IssuerPattern {
+    type Error = anyhow::Error;
+
+    fn try_from(pattern: String) -> Result<Self> {
+        let regex = Regex::new(&format!("^(?:{pattern})$"))
+            .with_context(|| format!("invalid issuer pattern {pattern}"))?;
+        Ok(Self { pattern, regex })
+    }
+}
+
+impl From<IssuerPattern> for String {
+    fn from(pattern: IssuerPattern) -> Self {
+        pattern.pattern
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    /// Identifies the caller in logs and metrics.
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub key: String,
+    /// Rate limit for all requests made with this key. Unlimited if unset.
+    #[serde(default)]
+    pub quota: Option<RateLimit>,
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("name", &self.name)
+            .field("key", &"<redacted>")
+            .field("quota", &self.quota)
+            .finish()
     }
 }
 
+/// What the training wheels signature covers. See [`crate::training_wheels::TrainingWheelsMessage`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TwSigningMode {
+    /// The proof and its public inputs hash. A TW key must only be used with a single VK, since
+    /// its signatures would otherwise vouch for proofs under any of them.
+    #[default]
+    V0,
+    /// The proof, its public inputs hash and the hash of the VK it was proved for.
+    V1,
+}
+
+/// Where a training wheels key is kept. See [`crate::training_wheels::signer`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TwSignerConfig {
+    /// A key file written by [`crate::training_wheels::signer::EncryptedKeyFile::encrypt`].
+    EncryptedFile {
+        path: String,
+        /// File holding the passphrase that the key is encrypted under.
+        passphrase_path: String,
+    },
+    /// A remote signer that holds the key.
+    Remote {
+        url: String,
+        /// The key's public key, which the remote signer's signatures are checked against.
+        public_key: String,
+        /// File holding a bearer token to authenticate to the remote signer with.
+        #[serde(default)]
+        auth_token_path: Option<String>,
+        #[serde(default = "default_remote_signer_timeout_secs")]
+        timeout_secs: u64,
+    },
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file with the server's certificate chain.
+    pub cert_path: String,
+    /// PEM file with the server's private key.
+    pub key_path: String,
+    /// PEM file with the CA certificates that client certificates must chain up to. If set, the
+    /// prove and validate routes reject clients without a valid certificate. The other routes,
+    /// e.g., the health checks, do not need one.
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+    /// How often to check the files above for changes. Changed files are reloaded without a
+    /// restart.
+    #[serde(default = "default_tls_reload_poll_secs")]
+    pub reload_poll_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct OidcProvider {
     pub iss: String,
-    pub endpoint_url: String,
+    /// Where to fetch the issuer's JWKs. If unset, this is the `jwks_uri` of the issuer's
+    /// `/.well-known/openid-configuration`.
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    /// Timeout for requests to `endpoint_url`, or for discovering it.
+    #[serde(default = "default_jwk_fetch_timeout_secs")]
+    pub fetch_timeout_secs: u64,
+    /// The JWT claims that requests for this issuer may use as `uid_key`, e.g., `oid` or
+    /// `preferred_username`. The claims must hold strings.
+    #[serde(default = "default_uid_keys")]
+    pub uid_keys: Vec<String>,
+    /// The `aud`s that JWTs from this issuer may have. Any `aud` is allowed if empty.
+    #[serde(default)]
+    pub allowed_auds: Vec<String>,
+    /// The largest `exp_horizon_secs` that requests for this issuer may use, if any.
+    #[serde(default)]
+    pub max_exp_horizon_secs: Option<u64>,
+    /// How far in the future a JWT's `iat` may be, for issuers whose clocks run ahead.
+    #[serde(default)]
+    pub iat_leeway_secs: u64,
+    /// How far in the past a JWT's `exp` may be.
+    #[serde(default = "default_exp_leeway_secs")]
+    pub exp_leeway_secs: u64,
+    /// Whether JWTs must have `email_verified: true` whatever the `uid_key`. It is always required
+    /// for `email`.
+    #[serde(default)]
+    pub require_email_verified: bool,
+    /// Whether requests for this issuer may set `skip_aud_checks`.
+    #[serde(default = "default_true")]
+    pub allow_skip_aud_checks: bool,
+}
+
+impl OidcProvider {
+    pub fn policy(&self) -> IssuerPolicy {
+        IssuerPolicy {
+            uid_keys: self.uid_keys.clone(),
+            allowed_auds: self.allowed_auds.clone(),
+            max_exp_horizon_secs: self.max_exp_horizon_secs,
+            iat_leeway_secs: self.iat_leeway_secs,
+            exp_leeway_secs: self.exp_leeway_secs,
+            require_email_verified: self.require_email_verified,
+            allow_skip_aud_checks: self.allow_skip_aud_checks,
+        }
+    }
+}
+
+/// The per-issuer part of the training wheels checks; see the fields of [`OidcProvider`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct IssuerPolicy {
+    pub uid_keys: Vec<String>,
+    pub allowed_auds: Vec<String>,
+    pub max_exp_horizon_secs: Option<u64>,
+    pub iat_leeway_secs: u64,
+    pub exp_leeway_secs: u64,
+    pub require_email_verified: bool,
+    pub allow_skip_aud_checks: bool,
+}
+
+impl Default for IssuerPolicy {
+    fn default() -> Self {
+        Self {
+            uid_keys: default_uid_keys(),
+            allowed_auds: vec![],
+            max_exp_horizon_secs: None,
+            iat_leeway_secs: 0,
+            exp_leeway_secs: default_exp_leeway_secs(),
+            require_email_verified: false,
+            allow_skip_aud_checks: true,
+        }
+    }
 }
@@ -0,0 +1,89 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    config::{self, ProverServiceConfig},
+    jwk_fetching::{self, JwkFetchSettings},
+    metrics,
+    state::{ProverServiceSecrets, ProverServiceState},
+};
+use anyhow::Result;
+use aptos_crypto::ValidCryptoMaterialStringExt;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task::JoinHandle,
+};
+use tracing::{error, info};
+
+/// Only one reload runs at a time, so that two of them do not both start JWK refresh loops.
+static RELOADING: Lazy<tokio::sync::Mutex<()>> = Lazy::new(Default::default);
+
+/// Read the config file and the training wheels keys again, and swap them in.
+pub async fn reload_config(state: &ProverServiceState) -> Result<()> {
+    apply_config(state, config::load_config()?).await
+}
+
+/// Swap in `config`, along with the training wheels keys it points to, and start or stop fetching
+/// JWKs for the OIDC providers that it added or removed. If the keys do not load, or none of them
+/// has the active key id, keeps the old config and keys. Cached proofs are dropped, since they
+/// were checked against the old config's policies and signed with the old keys.
+pub async fn apply_config(state: &ProverServiceState, config: ProverServiceConfig) -> Result<()> {
+    let _reloading = RELOADING.lock().await;
+
+    let tw_keys = ProverServiceSecrets::load(&config)?.into_tw_keys(&config)?;
+    jwk_fetching::update_jwk_fetching(
+        &config.oidc_providers,
+        JwkFetchSettings::from_config(&config),
+    )
+    .await;
+
+    state.config.set(config);
+    state.tw_keys.set(tw_keys);
+    state.proof_cache.clear();
+    Ok(())
+}
+
+/// [`reload_config`], logging and counting the outcome.
+pub async fn reload_config_and_log(state: &ProverServiceState) -> Result<()> {
+    info!("Reloading the config");
+    let result = reload_config(state).await;
+    match &result {
+        Ok(()) => {
+            metrics::CONFIG_RELOADS
+                .with_label_values(&["success"])
+                .inc();
+            let tw_keys = state.tw_keys.get();
+            info!(
+                active_tw_key_id = tw_keys.active_key_id,
+                active_tw_pk = tw_keys
+                    .active()
+                    .verification_key
+                    .to_encoded_string()
+                    .unwrap(),
+                "Reloaded the config"
+            );
+        }
+        Err(e) => {
+            metrics::CONFIG_RELOADS
+                .with_label_values(&["failure"])
+                .inc();
+            error!(
+                error = format!("{e:#}"),
+                "Failed to reload the config; still using the old one"
+            );
+        }
+    }
+    result
+}
+
+/// Reload the config every time the process receives SIGHUP.
+pub fn spawn_sighup_reload_loop(state: Arc<ProverServiceState>) -> JoinHandle<()> {
+    let mut sighup = signal(SignalKind::hangup()).expect("Couldn't listen for SIGHUP");
+    tokio::spawn(async move {
+        while sighup.recv().await.is_some() {
+            info!("Received SIGHUP");
+            let _ = reload_config_and_log(&state).await;
+        }
+    })
+}
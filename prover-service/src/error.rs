@@ -1,8 +1,18 @@
 // Copyright © Aptos Foundation
 
-use crate::{api::ProverServiceResponse, logging};
-use axum::{extract::rejection::JsonRejection, http::StatusCode, response::IntoResponse, Json};
+use crate::{
+    api::{ErrorCode, ProverServiceResponse},
+    logging,
+    prover_pool::AdmissionError,
+};
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use rust_rapidsnark::ProverError;
+use std::{fmt::Display, time::Duration};
 use tracing::{error, warn};
 
 // We derive `thiserror::Error`
@@ -29,23 +39,106 @@ impl IntoResponse for ApiError {
     }
 }
 
+/// An error tagged with the [`ErrorCode`] to report to the client. It displays exactly like the
+/// error it wraps, and can be recovered from an `anyhow::Error` (even one with context added on
+/// top) via `downcast_ref`.
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub error: anyhow::Error,
+}
+
+impl CodedError {
+    pub fn new(code: ErrorCode, error: impl Into<anyhow::Error>) -> Self {
+        Self {
+            code,
+            error: error.into(),
+        }
+    }
+
+    pub fn msg<M>(code: ErrorCode, message: M) -> Self
+    where
+        M: Display + std::fmt::Debug + Send + Sync + 'static,
+    {
+        Self::new(code, anyhow::Error::msg(message))
+    }
+}
+
+/// Trait to easily tag the error of a result with an [`ErrorCode`].
+pub trait WithErrorCode<T> {
+    fn with_error_code(self, code: ErrorCode) -> anyhow::Result<T>;
+}
+
+impl<T, E> WithErrorCode<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn with_error_code(self, code: ErrorCode) -> anyhow::Result<T> {
+        self.map_err(|error| CodedError::new(code, error).into())
+    }
+}
+
 /// The point of this struct is to have an error which knows which HTTP code to return.
 #[derive(Debug)]
 pub struct ErrorWithCode {
     pub error: anyhow::Error,
     pub code: Option<StatusCode>,
+    /// If set, sent to the client as a `Retry-After` header.
+    pub retry_after: Option<Duration>,
 }
 
 impl ErrorWithCode {
     pub fn context(self, s: &str) -> ErrorWithCode {
         Self {
             error: self.error.context(String::from(s)),
-            code: self.code,
+            ..self
+        }
+    }
+
+    pub fn retry_after(self, retry_after: Duration) -> ErrorWithCode {
+        Self {
+            retry_after: Some(retry_after),
+            ..self
         }
     }
 
+    /// The explicitly-set HTTP code if there is one, and otherwise the one implied by the error
+    /// code the error was tagged with.
     pub fn code(&self) -> StatusCode {
-        self.code.unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        self.code
+            .or_else(|| self.tagged_error_code().map(|code| code.status_code()))
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// The error code the error was tagged with, or a generic one matching the HTTP code.
+    pub fn error_code(&self) -> ErrorCode {
+        self.tagged_error_code()
+            .unwrap_or_else(|| generic_error_code(self.code()))
+    }
+
+    /// The body of the error response, without the HTTP code and headers.
+    pub fn to_response_body(&self) -> ProverServiceResponse {
+        ProverServiceResponse::Error {
+            error_code: self.error_code(),
+            message: self.error.to_string(),
+        }
+    }
+
+    fn tagged_error_code(&self) -> Option<ErrorCode> {
+        self.error
+            .downcast_ref::<CodedError>()
+            .map(|coded_error| coded_error.code)
+    }
+}
+
+fn generic_error_code(code: StatusCode) -> ErrorCode {
+    if code == StatusCode::SERVICE_UNAVAILABLE {
+        ErrorCode::ProverBusy
+    } else if code.is_client_error() {
+        ErrorCode::InvalidRequest
+    } else {
+        ErrorCode::InternalError
     }
 }
 
@@ -57,6 +150,7 @@ where
         Self {
             error: error.into(),
             code: None,
+            retry_after: None,
         }
     }
 }
@@ -65,6 +159,7 @@ pub fn bad_request(error: anyhow::Error) -> ErrorWithCode {
     ErrorWithCode {
         error,
         code: Some(StatusCode::BAD_REQUEST),
+        retry_after: None,
     }
 }
 
@@ -72,6 +167,7 @@ pub fn server_error(error: anyhow::Error) -> ErrorWithCode {
     ErrorWithCode {
         error,
         code: Some(StatusCode::INTERNAL_SERVER_ERROR),
+        retry_after: None,
     }
 }
 
@@ -79,9 +175,29 @@ pub fn service_unavailable(error: anyhow::Error) -> ErrorWithCode {
     ErrorWithCode {
         error,
         code: Some(StatusCode::SERVICE_UNAVAILABLE),
+        retry_after: None,
     }
 }
 
+/// A request that was turned away by the prover queue. Tells the client when to come back.
+pub fn prover_busy(error: AdmissionError, retry_after: Duration) -> ErrorWithCode {
+    service_unavailable(CodedError::new(ErrorCode::ProverBusy, error).into())
+        .retry_after(retry_after)
+}
+
+/// A request that exceeded one of the rate limits. Tells the client when to come back.
+pub fn rate_limited(scope: &str, retry_after: Duration) -> ErrorWithCode {
+    let retry_after = Duration::from_secs(retry_after.as_secs_f64().ceil().min(86400.0) as u64);
+    ErrorWithCode::from(CodedError::msg(
+        ErrorCode::RateLimited,
+        format!(
+            "Rate limit per {scope} exceeded; retry in {}s",
+            retry_after.as_secs()
+        ),
+    ))
+    .retry_after(retry_after)
+}
+
 /// Trait to easily convert results into results that know a code to return.
 /// If the wrapped error type already knows its code, do not override.
 pub trait ThrowCodeOnError<T> {
@@ -93,6 +209,7 @@ impl<T> ThrowCodeOnError<T> for Result<T, anyhow::Error> {
         self.map_err(|error| ErrorWithCode {
             error,
             code: Some(code),
+            retry_after: None,
         })
     }
 }
@@ -112,13 +229,15 @@ impl IntoResponse for ErrorWithCode {
             error!(error = self.error.to_string(), "Responding with error");
         }
 
-        (
-            self.code(),
-            Json(ProverServiceResponse::Error {
-                message: self.error.to_string(),
-            }),
-        )
-            .into_response()
+        let mut response = (self.code(), Json(self.to_response_body())).into_response();
+
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after.as_secs().into());
+        }
+
+        response
     }
 }
 
@@ -133,6 +252,7 @@ pub fn make_error(
     (
         code,
         Json(ProverServiceResponse::Error {
+            error_code: generic_error_code(code),
             message: format!("{message}\n{e_description}"),
         }),
     )
@@ -140,15 +260,15 @@ pub fn make_error(
 
 pub fn handle_prover_lib_error(e: ProverError) -> ErrorWithCode {
     match e {
-        ProverError::InvalidInput => bad_request(e.into())
+        ProverError::InvalidInput => bad_request(CodedError::new(ErrorCode::InvalidRequest, e).into())
             .context("Input is invalid or malformed"),
 
-        ProverError::WitnessGenerationBinaryProblem => server_error(e.into()).context("Problem with the witness generation binary"),
+        ProverError::WitnessGenerationBinaryProblem => server_error(CodedError::new(ErrorCode::WitnessGenFailed, e).into()).context("Problem with the witness generation binary"),
 
-        ProverError::WitnessGenerationInvalidCurve => server_error(e.into())
+        ProverError::WitnessGenerationInvalidCurve => server_error(CodedError::new(ErrorCode::WitnessGenFailed, e).into())
             .context("The generated witness file uses a different curve than bn128, which is currently the only supported curve."),
 
-        ProverError::Unknown(s) => server_error(e.into())
+        ProverError::Unknown(s) => server_error(CodedError::new(ErrorCode::ProverFailed, e).into())
             .context(&format!("Unknown error: {s}")),
     }
 }
@@ -156,12 +276,12 @@ pub fn handle_prover_lib_error(e: ProverError) -> ErrorWithCode {
 #[macro_export]
 macro_rules! bail {
     ($msg:literal $(,)?) => {
-        return anyhow::Result::Err($crate::error::ErrorWithCode{error: anyhow::anyhow!($msg), code: None})
+        return anyhow::Result::Err($crate::error::ErrorWithCode{error: anyhow::anyhow!($msg), code: None, retry_after: None})
     };
     ($err:expr $(,)?) => {
-        return anyhow::Result::Err($crate::error::ErrorWithCode{anyhow::anyhow!($err), code: None})
+        return anyhow::Result::Err($crate::error::ErrorWithCode{error: anyhow::anyhow!($err), code: None, retry_after: None})
     };
     ($fmt:expr, $($arg:tt)*) => {
-        return anyhow::Result::Err($crate::error::ErrorWithCode{error: anyhow::anyhow!($fmt, $($arg)*), code: None})
+        return anyhow::Result::Err($crate::error::ErrorWithCode{error: anyhow::anyhow!($fmt, $($arg)*), code: None, retry_after: None})
     };
 }
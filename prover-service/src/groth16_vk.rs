@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Result};
+use aptos_crypto::HashValue;
 use ark_bn254::{Fq, Fq2, G1Projective, G2Projective};
 use ark_ff::PrimeField;
 use num_bigint::BigUint;
@@ -77,6 +78,17 @@ fn test_local_vk_load_convert() {
     assert_eq!(expected, actual);
 }
 
+#[test]
+fn test_vk_hash() {
+    let local_vk_json = include_str!("../resources/202405_vk.vkey");
+    let local_vk: SnarkJsGroth16VerificationKey = serde_json::from_str(local_vk_json).unwrap();
+    let vk_hash = local_vk.try_as_onchain_repr().unwrap().hash().unwrap();
+    assert_eq!(
+        vk_hash.to_hex(),
+        "91d3eacd6b9cd732b9fdb7dfa918d14306609beb6ae08a774840272774cc83e2"
+    );
+}
+
 /// This is not a UT, but a tool to convert a .vkey to its on-chain representation and save in a file.
 #[test]
 fn groth16_vk_rewriter() {
@@ -109,6 +121,32 @@ pub struct OnChainGroth16VerificationKey {
     pub data: VKeyData,
 }
 
+impl OnChainGroth16VerificationKey {
+    /// SHA3-256 of the VK as BCS-serialized on chain, i.e., of its points in the field order of
+    /// `0x1::keyless_account::Groth16VerificationKey`.
+    pub fn hash(&self) -> Result<HashValue> {
+        let VKeyData {
+            alpha_g1,
+            beta_g2,
+            delta_g2,
+            gamma_abc_g1,
+            gamma_g2,
+        } = &self.data;
+        let decode = |point: &String| hex::decode(point.trim_start_matches("0x"));
+        let onchain_vk = (
+            decode(alpha_g1)?,
+            decode(beta_g2)?,
+            decode(gamma_g2)?,
+            decode(delta_g2)?,
+            gamma_abc_g1
+                .iter()
+                .map(decode)
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+        Ok(HashValue::sha3_256_of(&bcs::to_bytes(&onchain_vk)?))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct VKeyData {
     pub alpha_g1: String,
@@ -1,12 +1,22 @@
 // Copyright © Aptos Foundation
 
 use crate::{
-    api::{ProverServiceResponse, RequestInput},
-    error::{self, ErrorWithCode, ThrowCodeOnError},
-    input_processing::derive_circuit_input_signals,
-    metrics,
+    api::{
+        CreateJobRequest, ErrorCode, IssuerInfo, JobResponse, JobStatus, ProverServiceResponse,
+        RequestInput, ServiceFeatures, ServiceMetadata, SetupInfo, TrainingWheelsInfo,
+        ValidationResponse,
+    },
+    config::DEFAULT_UID_KEYS,
+    config_reload,
+    error::{self, CodedError, ErrorWithCode, ThrowCodeOnError, WithErrorCode},
+    health::{self, HealthReport},
+    input_processing::{derive_circuit_input_signals, derive_unpadded_circuit_input_signals},
+    proof_cache::ProofCacheKey,
+    prover_pool::QueueTicket,
+    rate_limit::ClientIp,
+    setup::{self, CircuitSetup},
     state::ProverServiceState,
-    training_wheels,
+    training_wheels::{self, TrainingWheelsMessage},
     witness_gen::witness_gen,
 };
 use anyhow::Result;
@@ -14,79 +24,482 @@ use aptos_types::{
     keyless::{G1Bytes, G2Bytes, Groth16Proof},
     transaction::authenticator::EphemeralSignature,
 };
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
 use axum_extra::extract::WithRejection;
 
 use crate::proving::prove;
 use aptos_crypto::hash::CryptoHash;
+use aptos_crypto::ValidCryptoMaterialStringExt;
 use aptos_keyless_common::logging;
 use aptos_keyless_common::logging::HasLoggableError;
+use aptos_keyless_common::PoseidonHash;
+use futures::future::join_all;
 use maplit2::hashmap;
-use serde::Deserialize;
-use std::{sync::Arc, time::Instant};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
 use uuid::Uuid;
 
 pub async fn prove_handler(
     State(state): State<Arc<ProverServiceState>>,
     WithRejection(Json(body), _): WithRejection<Json<RequestInput>, error::ApiError>,
 ) -> Result<Json<ProverServiceResponse>, ErrorWithCode> {
-    let start_time: Instant = Instant::now();
-    metrics::REQUEST_QUEUE_TIME_SECS.observe(start_time.elapsed().as_secs_f64());
+    // Admit the request before doing any work for it, so that a full queue sheds load early.
+    let admission = admit(&state, &body)?;
+
+    let response =
+        logging::run_with_empty_logger_context(respond(&state, admission, &body)).await?;
+    Ok(Json(response))
+}
+
+/// Proves every request in the batch independently, and responds with one `ProverServiceResponse`
+/// per request, in the same order. A request that fails does not fail the others.
+///
+/// Each request counts against the per-client-IP rate limit. The rate limiting middleware charged
+/// for the first one, so requests past the client's remaining quota fail with `RateLimited`.
+pub async fn prove_batch_handler(
+    State(state): State<Arc<ProverServiceState>>,
+    client_ip: Option<Extension<ClientIp>>,
+    WithRejection(Json(batch), _): WithRejection<Json<Vec<RequestInput>>, error::ApiError>,
+) -> Result<Json<Vec<ProverServiceResponse>>, ErrorWithCode> {
+    if batch.len() > state.config().max_prove_batch_size {
+        return Err(error::bad_request(
+            CodedError::msg(
+                ErrorCode::InvalidRequest,
+                format!(
+                    "Batch of {} requests exceeds the max batch size of {}",
+                    batch.len(),
+                    state.config().max_prove_batch_size
+                ),
+            )
+            .into(),
+        ));
+    }
+
+    // Admit all requests up front, so that a batch only takes the queue slots that are free.
+    let admissions: Vec<_> = batch
+        .iter()
+        .enumerate()
+        .map(|(i, body)| {
+            if let Some(Extension(ClientIp(ip))) = client_ip.filter(|_| i > 0) {
+                state.rate_limiters.check_client_ip(ip)?;
+            }
+            admit(&state, body)
+        })
+        .collect();
+
+    // Prove each request in its own task, so that the requests of a batch run in parallel.
+    let tasks: Vec<_> = batch
+        .into_iter()
+        .zip(admissions)
+        .map(|(body, admission)| {
+            let state = state.clone();
+            tokio::spawn(logging::run_with_empty_logger_context(async move {
+                let response = match admission {
+                    Ok(admission) => respond(&state, admission, &body).await,
+                    Err(e) => Err(e),
+                };
+                response.unwrap_or_else(|e| e.to_response_body())
+            }))
+        })
+        .collect();
+
+    let responses = join_all(tasks)
+        .await
+        .into_iter()
+        .map(|response| response.unwrap_or_else(|e| ErrorWithCode::from(e).to_response_body()))
+        .collect();
+
+    Ok(Json(responses))
+}
+
+/// Starts proving the request in the background, and responds right away with the id of the job
+/// to poll with `GET /v1/jobs/{id}`.
+pub async fn create_job_handler(
+    State(state): State<Arc<ProverServiceState>>,
+    WithRejection(Json(body), _): WithRejection<Json<CreateJobRequest>, error::ApiError>,
+) -> Result<(StatusCode, Json<JobResponse>), ErrorWithCode> {
+    let CreateJobRequest {
+        request,
+        callback_url,
+    } = body;
+    let admission = admit(&state, &request)?;
+    let job_id = state
+        .jobs
+        .create(callback_url)
+        .with_error_code(ErrorCode::InvalidRequest)?;
+
+    tokio::spawn({
+        let state = state.clone();
+        let job_id = job_id.clone();
+        logging::run_with_empty_logger_context(async move {
+            state.jobs.set_running(&job_id);
+            let response = respond(&state, admission, &request)
+                .await
+                .unwrap_or_else(|e| e.to_response_body());
+            state.jobs.finish(&job_id, response).await;
+        })
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(JobResponse {
+            job_id,
+            status: JobStatus::Pending,
+            response: None,
+        }),
+    ))
+}
+
+pub async fn get_job_handler(
+    State(state): State<Arc<ProverServiceState>>,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobResponse>, ErrorWithCode> {
+    let job = state.jobs.get(&job_id).ok_or_else(|| {
+        CodedError::msg(
+            ErrorCode::JobNotFound,
+            format!("No job with id {job_id}, or it has expired"),
+        )
+    })?;
+    Ok(Json(job))
+}
+
+/// How a request gets its response: straight from the proof cache, or through the prover queue
+/// of the setup it selected, as it was when the request arrived.
+enum Admission {
+    Cached(Box<ProverServiceResponse>),
+    Queued {
+        setup: Arc<CircuitSetup>,
+        queue_ticket: QueueTicket,
+        cache_key: ProofCacheKey,
+    },
+}
+
+/// Identical retries are answered from the proof cache, without taking a place in the queue.
+fn admit(state: &ProverServiceState, body: &RequestInput) -> Result<Admission, ErrorWithCode> {
+    if state.is_shutting_down() {
+        return Err(CodedError::msg(
+            ErrorCode::ShuttingDown,
+            "Prover service is shutting down; try again",
+        )
+        .into());
+    }
+    let setup = select_setup(state, body)?;
+    let cache_key = state.proof_cache.key(CryptoHash::hash(body), setup.vk_hash);
+    if state.config().enable_proof_cache {
+        if let Some(response) = state.proof_cache.get(&cache_key) {
+            return Ok(Admission::Cached(Box::new(response)));
+        }
+    }
+    let queue_ticket = enqueue(state, &setup)?;
+    Ok(Admission::Queued {
+        setup,
+        queue_ticket,
+        cache_key,
+    })
+}
+
+async fn respond(
+    state: &ProverServiceState,
+    admission: Admission,
+    body: &RequestInput,
+) -> Result<ProverServiceResponse, ErrorWithCode> {
+    match admission {
+        Admission::Cached(response) => Ok(*response),
+        Admission::Queued {
+            setup,
+            queue_ticket,
+            cache_key,
+        } => prove_request(state, &setup, queue_ticket, cache_key, body).await,
+    }
+}
+
+fn select_setup(
+    state: &ProverServiceState,
+    body: &RequestInput,
+) -> Result<Arc<CircuitSetup>, ErrorWithCode> {
+    state.setups.select(body.setup.as_deref()).ok_or_else(|| {
+        CodedError::msg(
+            ErrorCode::SetupNotFound,
+            format!(
+                "No setup named {}; see /meta for the available ones",
+                body.setup.as_deref().unwrap_or_default()
+            ),
+        )
+        .into()
+    })
+}
+
+fn enqueue(state: &ProverServiceState, setup: &CircuitSetup) -> Result<QueueTicket, ErrorWithCode> {
+    setup.prover_pool.enqueue().map_err(|e| {
+        error::prover_busy(
+            e,
+            Duration::from_secs(state.config().prover_queue_retry_after_secs),
+        )
+    })
+}
+
+/// Validates, derives circuit inputs for, proves and signs a single request that has already
+/// been admitted into the prover queue.
+async fn prove_request(
+    state: &ProverServiceState,
+    setup: &CircuitSetup,
+    queue_ticket: QueueTicket,
+    cache_key: ProofCacheKey,
+    body: &RequestInput,
+) -> Result<ProverServiceResponse, ErrorWithCode> {
+    let _span = logging::new_span_extra_attrs(
+        "HandleRequest",
+        hashmap! {
+            "session_id" => Uuid::new_v4().to_string()[0..8].to_string(),
+            "req_hash" => cache_key.request_hash.to_hex(),
+        },
+    );
+
+    let input =
+        training_wheels::preprocess_and_validate_request(state, &setup.circuit_config, body)
+            .await
+            .log_err()
+            .with_status(StatusCode::BAD_REQUEST)?;
+
+    state.rate_limiters.check_verified_input(&input)?;
+
+    let (circuit_input_signals, public_inputs_hash) =
+        derive_circuit_input_signals(input, &setup.circuit_config).log_err()?;
 
+    let witness_file = witness_gen(&setup.config, &circuit_input_signals)
+        .await
+        .with_error_code(ErrorCode::WitnessGenFailed)
+        .log_err()?;
+
+    let proof = prove(state, setup, queue_ticket, witness_file, public_inputs_hash)
+        .await
+        .log_err()?;
+
+    // In signing mode v0, only the proof and its statement are signed, not the VK. As a result,
+    // whenever the VK changes on-chain, the TW PK must change too. Otherwise, an old proof
+    // computed for an old VK will pass the TW signature check, even though this proof will not
+    // verify under the new VK. Mode v1 signs the VK hash too.
+    let signing_mode = state.config().training_wheels_signing_mode;
+    let message =
+        TrainingWheelsMessage::new(signing_mode, proof, public_inputs_hash, &setup.vk_hash);
+    let tw_keys = state.tw_keys.get();
+    let training_wheels_signature = EphemeralSignature::ed25519(
+        tw_keys
+            .active()
+            .signer
+            .sign(&message)
+            .await
+            .with_error_code(ErrorCode::SigningFailed)
+            .log_err()?,
+    );
+
+    let response = ProverServiceResponse::Success {
+        proof,
+        public_inputs_hash,
+        training_wheels_signature: bcs::to_bytes(&training_wheels_signature).unwrap(),
+    };
+
+    if state.config().enable_debug_checks {
+        assert!(training_wheels::verify(
+            &response,
+            &tw_keys.active().verification_key,
+            signing_mode,
+            &setup.vk_hash
+        )
+        .is_ok());
+    }
+
+    if state.config().enable_proof_cache {
+        state
+            .proof_cache
+            .insert(cache_key, body.exp_date_secs, &response);
+    }
+
+    Ok(response)
+}
+
+/// Runs the same checks as `prove_handler` up to and including padding the circuit inputs, but
+/// skips witness generation and proving. Lets clients check a request cheaply before proving it.
+pub async fn validate_handler(
+    State(state): State<Arc<ProverServiceState>>,
+    WithRejection(Json(body), _): WithRejection<Json<RequestInput>, error::ApiError>,
+) -> Result<Json<ValidationResponse>, ErrorWithCode> {
     logging::run_with_empty_logger_context(async {
         let _span = logging::new_span_extra_attrs(
-            "HandleRequest",
+            "HandleValidateRequest",
             hashmap! {
                 "session_id" => Uuid::new_v4().to_string()[0..8].to_string(),
                 "req_hash" => CryptoHash::hash(&body).to_hex(),
             },
         );
 
-        let input = training_wheels::preprocess_and_validate_request(state.as_ref(), &body)
-            .await
-            .log_err()
-            .with_status(StatusCode::BAD_REQUEST)?;
+        let setup = select_setup(&state, &body)?;
+        let input = training_wheels::preprocess_and_validate_request(
+            state.as_ref(),
+            &setup.circuit_config,
+            &body,
+        )
+        .await
+        .log_err()
+        .with_status(StatusCode::BAD_REQUEST)?;
 
         let (circuit_input_signals, public_inputs_hash) =
-            derive_circuit_input_signals(input, state.circuit_config()).log_err()?;
+            derive_unpadded_circuit_input_signals(&input, &setup.circuit_config).log_err()?;
 
-        let witness_file = witness_gen(&state.config, &circuit_input_signals).log_err()?;
+        let mut field_lengths = circuit_input_signals.lengths(&setup.circuit_config);
+        field_lengths.sort_by(|a, b| a.signal.cmp(&b.signal));
 
-        let proof = prove(state.as_ref(), witness_file, public_inputs_hash)
-            .await
+        let too_long: Vec<String> = field_lengths
+            .iter()
+            .filter(|length| !length.fits())
+            .map(|length| {
+                format!(
+                    "{} ({} > {})",
+                    length.signal,
+                    length.len,
+                    length.max_len.unwrap_or_default()
+                )
+            })
+            .collect();
+        if !too_long.is_empty() {
+            Err::<(), _>(anyhow::Error::from(CodedError::msg(
+                ErrorCode::JwtFieldTooLong,
+                format!("Circuit inputs too long: {}", too_long.join(", ")),
+            )))
             .log_err()?;
+        }
 
-        // We should've signed the VK too but, unfortunately, we realized this too late.
-        // As a result, whenever the VK changes on-chain, the TW PK must change too.
-        // Otherwise, an old proof computed for an old VK will pass the TW signature check, even though this proof will not verify under the new VK.
-        let training_wheels_signature = EphemeralSignature::ed25519(
-            training_wheels::sign(&state.tw_keys.signing_key, proof, public_inputs_hash)
-                .map_err(anyhow::Error::from)
-                .log_err()?,
-        );
+        // Whatever else `/v0/prove` would reject when padding, reject here too.
+        circuit_input_signals
+            .pad(&setup.circuit_config)
+            .with_error_code(ErrorCode::JwtFieldTooLong)
+            .log_err()?;
 
-        let response = ProverServiceResponse::Success {
-            proof,
-            public_inputs_hash,
-            training_wheels_signature: bcs::to_bytes(&training_wheels_signature).unwrap(),
-        };
+        Ok(Json(ValidationResponse {
+            public_inputs_hash: PoseidonHash::try_from_fr(&public_inputs_hash)?,
+            nonce: input.jwt.payload.nonce.clone(),
+            field_lengths,
+        }))
+    })
+    .await
+}
+
+/// Query of `POST /admin/reload_setup`.
+#[derive(Deserialize)]
+pub struct ReloadSetupQuery {
+    /// The setup to reload. If unset, all of them.
+    setup: Option<String>,
+}
 
-        if state.config.enable_debug_checks {
-            assert!(training_wheels::verify(&response, &state.tw_keys.verification_key).is_ok());
+/// Reloads circuit setups from disk, and swaps each in once it passes its self-test. Requests
+/// already admitted finish on the old setup. Served on the admin port, on localhost only. Responds
+/// with the setups that are served afterwards.
+pub async fn reload_setup_handler(
+    State(state): State<Arc<ProverServiceState>>,
+    Query(query): Query<ReloadSetupQuery>,
+) -> Result<Json<Vec<SetupInfo>>, ErrorWithCode> {
+    let names = match query.setup {
+        Some(name) => {
+            let setup = state.setups.select(Some(&name)).ok_or_else(|| {
+                CodedError::msg(ErrorCode::SetupNotFound, format!("No setup named {name}"))
+            })?;
+            vec![setup.name.clone()]
         }
+        None => state
+            .setups
+            .all()
+            .iter()
+            .map(|setup| setup.name.clone())
+            .collect(),
+    };
+    for name in names {
+        setup::reload_and_log(&state, &name).await?;
+    }
+    Ok(Json(state.setups.infos()))
+}
 
-        Ok(Json(response))
-    })
-    .await
+/// Re-reads the config file and the training wheels keys, and swaps them in. Served on the admin
+/// port, on localhost only. Settings that are only read at startup keep their old values.
+pub async fn reload_config_handler(
+    State(state): State<Arc<ProverServiceState>>,
+) -> Result<Json<ServiceMetadata>, ErrorWithCode> {
+    config_reload::reload_config_and_log(&state).await?;
+    Ok(Json(service_metadata(&state)))
+}
+
+pub async fn meta_handler(State(state): State<Arc<ProverServiceState>>) -> Json<ServiceMetadata> {
+    Json(service_metadata(&state))
+}
+
+pub fn service_metadata(state: &ProverServiceState) -> ServiceMetadata {
+    let config = state.config();
+    let tw_keys = state.tw_keys.get();
+    ServiceMetadata {
+        git_commit: config.git_commit.clone(),
+        setups: state.setups.infos(),
+        training_wheels: TrainingWheelsInfo {
+            key_id: tw_keys.active_key_id.clone(),
+            pubkey: tw_keys
+                .active()
+                .verification_key
+                .to_encoded_string()
+                .unwrap(),
+            on_chain_keyless_configuration: tw_keys.active().on_chain_repr.clone(),
+            signing_mode: config.training_wheels_signing_mode,
+        },
+        default_uid_keys: DEFAULT_UID_KEYS.map(String::from).to_vec(),
+        issuers: config
+            .oidc_providers
+            .iter()
+            .map(|provider| IssuerInfo {
+                iss: provider.iss.clone(),
+                policy: provider.policy(),
+            })
+            .collect(),
+        federated_issuer_patterns: config
+            .federated_issuer_patterns
+            .iter()
+            .map(|pattern| String::from(pattern.as_str()))
+            .collect(),
+        features: ServiceFeatures {
+            federated_jwks: config.enable_federated_jwks,
+            jwt_iat_not_in_future_check: config.enable_jwt_iat_not_in_future_check,
+            jwt_exp_not_in_the_past_check: config.enable_jwt_exp_not_in_the_past_check,
+            jwt_algorithms: config.allowed_jwt_algorithms.clone(),
+            api_key_required: state.api_keys.is_enabled(),
+            max_prove_batch_size: config.max_prove_batch_size,
+        },
+    }
 }
 
 /// Added on request by Christian: Kubernetes apparently needs a GET route to check whether
 /// this service is ready for requests.
+/// Kept for backwards compatibility; `/readyz` and `/livez` do the actual checks.
 pub async fn healthcheck_handler() -> (StatusCode, &'static str) {
-    // TODO: CHECK FOR A REAL STATUS OF PROVER HERE?
     (StatusCode::OK, "OK")
 }
 
+/// Fails (with a 503) until the prover key, VK, circuit config and JWKs for every configured
+/// OIDC provider are loaded.
+pub async fn readyz_handler(
+    State(state): State<Arc<ProverServiceState>>,
+) -> (StatusCode, Json<HealthReport>) {
+    let report = health::readiness(&state);
+    (report.status_code(), Json(report))
+}
+
+/// Fails (with a 503) if a prover worker looks wedged, so that Kubernetes restarts the pod.
+pub async fn livez_handler(
+    State(state): State<Arc<ProverServiceState>>,
+) -> (StatusCode, Json<HealthReport>) {
+    let report = health::liveness(&state);
+    (report.status_code(), Json(report))
+}
+
 /// On all unrecognized routes, return 404.
 pub async fn fallback_handler() -> (StatusCode, &'static str) {
     (StatusCode::NOT_FOUND, "Invalid route")
@@ -0,0 +1,167 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    config::OidcProvider, jwk_fetching::DECODING_KEY_CACHE, setup::CircuitSetup,
+    state::ProverServiceState,
+};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+
+/// Outcome of a single readiness or liveness sub-check.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HealthCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl HealthCheck {
+    fn new(name: &str, ok: bool, detail: impl ToString) -> Self {
+        Self {
+            name: String::from(name),
+            ok,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+/// Body returned by `/readyz` and `/livez`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HealthReport {
+    pub ok: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    pub fn from_checks(checks: Vec<HealthCheck>) -> Self {
+        Self {
+            ok: checks.iter().all(|check| check.ok),
+            checks,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        if self.ok {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// Whether this instance is able to serve prove requests right now.
+pub fn readiness(state: &ProverServiceState) -> HealthReport {
+    // The prover key, VK and circuit config are loaded into memory before we serve at all, but
+    // witness generation runs the setup's binary from disk for every request, and reloads read
+    // all of the setup's files again.
+    let setup_files: Vec<PathBuf> = state
+        .setups
+        .all()
+        .iter()
+        .flat_map(|setup| CircuitSetup::paths(&setup.config))
+        .collect();
+
+    HealthReport::from_checks(vec![
+        check_setup_files_present(&setup_files),
+        check_jwks_cached(&state.config().oidc_providers),
+        HealthCheck::new(
+            "not_shutting_down",
+            !state.is_shutting_down(),
+            if state.is_shutting_down() {
+                "shutting down"
+            } else {
+                "accepting requests"
+            },
+        ),
+    ])
+}
+
+/// Whether this instance is stuck and should be restarted.
+pub fn liveness(state: &ProverServiceState) -> HealthReport {
+    let deadline = Duration::from_secs(state.config().prover_liveness_deadline_secs);
+    // Workers of a replaced setup that are still finishing old requests can get wedged too.
+    let busy_workers: Vec<_> = state
+        .setups
+        .live()
+        .iter()
+        .flat_map(|setup| setup.prover_pool.busy_workers())
+        .collect();
+    HealthReport::from_checks(vec![check_no_wedged_workers(&busy_workers, deadline)])
+}
+
+/// Every file the setups are loaded from must still be on disk.
+pub fn check_setup_files_present(paths: &[PathBuf]) -> HealthCheck {
+    let missing: Vec<String> = paths
+        .iter()
+        .filter(|path| !path.is_file())
+        .map(|path| path.display().to_string())
+        .collect();
+
+    if missing.is_empty() {
+        HealthCheck::new(
+            "setup_files_present",
+            true,
+            format!("all {} setup file(s) present", paths.len()),
+        )
+    } else {
+        HealthCheck::new(
+            "setup_files_present",
+            false,
+            format!("missing: {}", missing.join(", ")),
+        )
+    }
+}
+
+/// Every configured provider must have at least one JWK in the cache.
+pub fn check_jwks_cached(oidc_providers: &[OidcProvider]) -> HealthCheck {
+    let missing: Vec<&str> = oidc_providers
+        .iter()
+        .filter(|provider| {
+            !DECODING_KEY_CACHE
+                .get(&provider.iss)
+                .is_some_and(|key_set| !key_set.is_empty())
+        })
+        .map(|provider| provider.iss.as_str())
+        .collect();
+
+    if missing.is_empty() {
+        HealthCheck::new(
+            "jwks_cached",
+            true,
+            format!("JWKs cached for all {} issuer(s)", oidc_providers.len()),
+        )
+    } else {
+        HealthCheck::new(
+            "jwks_cached",
+            false,
+            format!("no JWKs cached for: {}", missing.join(", ")),
+        )
+    }
+}
+
+/// No prover worker may stay checked out for longer than `deadline`.
+pub fn check_no_wedged_workers(
+    busy_workers: &[(usize, Duration)],
+    deadline: Duration,
+) -> HealthCheck {
+    let wedged: Vec<String> = busy_workers
+        .iter()
+        .filter(|(_, busy_for)| *busy_for > deadline)
+        .map(|(id, busy_for)| format!("worker {id} busy for {}s", busy_for.as_secs()))
+        .collect();
+
+    if wedged.is_empty() {
+        HealthCheck::new(
+            "prover_not_wedged",
+            true,
+            format!("{} prover worker(s) busy", busy_workers.len()),
+        )
+    } else {
+        HealthCheck::new(
+            "prover_not_wedged",
+            false,
+            format!("{} (deadline {}s)", wedged.join(", "), deadline.as_secs()),
+        )
+    }
+}
@@ -2,7 +2,7 @@
 
 use super::{field_parser::ParsedField, types::VerifiedInput};
 use crate::input_processing::field_parser::FieldParser;
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use aptos_keyless_common::input_processing::circuit_input_signals::{
     CircuitInputSignals, Unpadded,
 };
@@ -129,27 +129,34 @@ pub fn private_aud_value(input: &VerifiedInput) -> Result<String> {
         (true, Some(_)) => bail!("there is no aud-based recovery in aud-less mode"),
         (true, None) => Ok("".to_string()),
         (false, Some(v)) => Ok(v.clone()),
-        (false, None) => {
-            let aud = input.jwt.payload.aud.clone();
-            Ok(aud)
-        }
+        (false, None) => single_aud(input),
     }
 }
 
-pub fn override_aud_value(input: &VerifiedInput) -> String {
+pub fn override_aud_value(input: &VerifiedInput) -> Result<String> {
     if let Some(_v) = &input.idc_aud {
-        input.jwt.payload.aud.clone()
+        single_aud(input)
     } else {
-        String::from("")
+        Ok(String::from(""))
     }
 }
 
+fn single_aud(input: &VerifiedInput) -> Result<String> {
+    input
+        .jwt
+        .payload
+        .aud
+        .as_single()
+        .map(String::from)
+        .ok_or_else(|| anyhow!("the circuit does not support aud arrays"))
+}
+
 pub fn aud_signals(input: &VerifiedInput) -> Result<CircuitInputSignals<Unpadded>> {
     let parsed_field =
         FieldParser::find_and_parse_field(input.jwt_parts.payload_decoded()?.as_str(), "aud")?;
 
     let private_aud_value = private_aud_value(input)?;
-    let override_aud_value = override_aud_value(input);
+    let override_aud_value = override_aud_value(input)?;
 
     let mut result = CircuitInputSignals::new()
         .merge(whole_field_signals(&parsed_field, "aud")?)?
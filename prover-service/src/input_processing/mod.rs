@@ -9,12 +9,14 @@ pub mod types;
 use self::{
     field_check_input::field_check_input_signals, public_inputs_hash::compute_public_inputs_hash,
 };
+use crate::api::ErrorCode;
+use crate::error::WithErrorCode;
 use crate::input_processing::types::VerifiedInput;
 use anyhow::Result;
 use aptos_keyless_common::logging;
 use aptos_keyless_common::{
     input_processing::{
-        circuit_input_signals::{CircuitInputSignals, Padded},
+        circuit_input_signals::{CircuitInputSignals, Padded, Unpadded},
         config::CircuitConfig,
         encoding::*,
         sha::{compute_sha_padding_without_len, jwt_bit_len_binary, with_sha_padding_bytes},
@@ -28,13 +30,30 @@ pub fn derive_circuit_input_signals(
 ) -> Result<(CircuitInputSignals<Padded>, PoseidonHash), anyhow::Error> {
     let _span = logging::new_span("DeriveCircuitInputSignals");
 
+    let (circuit_input_signals, public_inputs_hash) =
+        derive_unpadded_circuit_input_signals(&input, config)?;
+
+    // add padding for global inputs
+    let padded = circuit_input_signals
+        .pad(config)
+        .with_error_code(ErrorCode::JwtFieldTooLong)?;
+
+    Ok((padded, PoseidonHash::try_from_fr(&public_inputs_hash)?))
+}
+
+/// Derive the circuit input signals, before they are padded to the lengths in `config`. Also
+/// returns the public inputs hash.
+pub fn derive_unpadded_circuit_input_signals(
+    input: &VerifiedInput,
+    config: &CircuitConfig,
+) -> Result<(CircuitInputSignals<Unpadded>, ark_bn254::Fr), anyhow::Error> {
     let jwt_parts = &input.jwt_parts;
     let epk_blinder_fr = input.epk_blinder_fr;
     let unsigned_jwt_with_padding =
         with_sha_padding_bytes(input.jwt_parts.unsigned_undecoded().as_bytes());
     let (ephemeral_pubkey_frs, ephemeral_pubkey_len) =
-        public_inputs_hash::compute_ephemeral_pubkey_frs(&input)?;
-    let public_inputs_hash = compute_public_inputs_hash(&input, config)?;
+        public_inputs_hash::compute_ephemeral_pubkey_frs(input)?;
+    let public_inputs_hash = compute_public_inputs_hash(input, config)?;
 
     let mut circuit_input_signals = CircuitInputSignals::new()
         // "global" inputs
@@ -82,13 +101,9 @@ pub fn derive_circuit_input_signals(
     }
     circuit_input_signals = circuit_input_signals
         .fr_input("public_inputs_hash", public_inputs_hash)
-        .merge(field_check_input_signals(&input)?)?;
+        .merge(field_check_input_signals(input)?)?;
 
-    // add padding for global inputs
-    let padded = circuit_input_signals.pad(config)?;
-    // "field check" input signals
-
-    Ok((padded, PoseidonHash::try_from_fr(&public_inputs_hash)?))
+    Ok((circuit_input_signals, public_inputs_hash))
 }
 
 #[cfg(test)]
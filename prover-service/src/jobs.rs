@@ -0,0 +1,266 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    api::{JobResponse, JobStatus, ProverServiceResponse},
+    state::ProverServiceState,
+};
+use anyhow::{anyhow, ensure, Result};
+use dashmap::DashMap;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, Url,
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// How often finished jobs that are older than the TTL are dropped.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Job {
+    status: JobStatus,
+    response: Option<ProverServiceResponse>,
+    callback_url: Option<String>,
+    /// When the job finished. Finished jobs are evicted once they are older than the TTL.
+    finished_at: Option<Instant>,
+}
+
+/// In-memory store for the jobs of the `/v1/jobs` API.
+///
+/// Pending and running jobs stay in the store until they finish; finished jobs expire `ttl` after
+/// they finished, and are evicted when they are looked up or by [`spawn_eviction_loop`], whichever
+/// comes first. Jobs do not survive a restart.
+///
+/// Anyone who can create a job picks its callback URL, so callbacks only go to
+/// `callback_allowed_hosts` if it is non-empty, and otherwise only to public IP addresses.
+pub struct JobStore {
+    jobs: DashMap<String, Job>,
+    ttl: Duration,
+    callback_allowed_hosts: Vec<String>,
+    http_client: reqwest::Client,
+}
+
+impl JobStore {
+    pub fn new(
+        ttl: Duration,
+        callback_timeout: Duration,
+        callback_allowed_hosts: Vec<String>,
+    ) -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(callback_timeout)
+            .redirect(redirect::Policy::none());
+        let http_client = if callback_allowed_hosts.is_empty() {
+            http_client.dns_resolver(Arc::new(PublicAddrResolver))
+        } else {
+            http_client
+        };
+        Self {
+            jobs: DashMap::new(),
+            ttl,
+            callback_allowed_hosts,
+            http_client: http_client
+                .build()
+                .expect("failed to build job callback HTTP client"),
+        }
+    }
+
+    /// Add a new pending job and return its id.
+    pub fn create(&self, callback_url: Option<String>) -> Result<String> {
+        if let Some(callback_url) = &callback_url {
+            self.check_callback_url(callback_url)?;
+        }
+
+        let job_id = Uuid::new_v4().to_string();
+        self.jobs.insert(
+            job_id.clone(),
+            Job {
+                status: JobStatus::Pending,
+                response: None,
+                callback_url,
+                finished_at: None,
+            },
+        );
+        Ok(job_id)
+    }
+
+    pub fn set_running(&self, job_id: &str) {
+        if let Some(mut job) = self.jobs.get_mut(job_id) {
+            job.status = JobStatus::Running;
+        }
+    }
+
+    /// Record the job's result, and notify its callback URL if it has one.
+    pub async fn finish(&self, job_id: &str, response: ProverServiceResponse) {
+        let status = match response {
+            ProverServiceResponse::Success { .. } => JobStatus::Done,
+            ProverServiceResponse::Error { .. } => JobStatus::Failed,
+        };
+
+        let callback_url = match self.jobs.get_mut(job_id) {
+            Some(mut job) => {
+                job.status = status;
+                job.response = Some(response);
+                job.finished_at = Some(Instant::now());
+                job.callback_url.clone()
+            }
+            None => return,
+        };
+
+        if let (Some(callback_url), Some(job_response)) = (callback_url, self.get(job_id)) {
+            self.notify(&callback_url, &job_response).await;
+        }
+    }
+
+    /// The job's current status, or `None` if there is no such job or it has expired.
+    pub fn get(&self, job_id: &str) -> Option<JobResponse> {
+        {
+            let job = self.jobs.get(job_id)?;
+            if !self.is_expired(&job) {
+                return Some(JobResponse {
+                    job_id: String::from(job_id),
+                    status: job.status,
+                    response: job.response.clone(),
+                });
+            }
+        }
+        // Not while holding the read guard above, which would deadlock.
+        self.jobs.remove_if(job_id, |_, job| self.is_expired(job));
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn evict_expired(&self) {
+        self.jobs.retain(|_, job| !self.is_expired(job));
+    }
+
+    fn is_expired(&self, job: &Job) -> bool {
+        job.finished_at
+            .is_some_and(|finished_at| finished_at.elapsed() > self.ttl)
+    }
+
+    /// Host names are only resolved when the callback is sent, by [`PublicAddrResolver`], so that
+    /// they cannot be pointed elsewhere in the meantime.
+    fn check_callback_url(&self, callback_url: &str) -> Result<()> {
+        let url = Url::parse(callback_url).map_err(|e| anyhow!("invalid callback URL: {e}"))?;
+        ensure!(
+            matches!(url.scheme(), "http" | "https"),
+            "callback URL must be http(s)"
+        );
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("callback URL has no host"))?;
+
+        if !self.callback_allowed_hosts.is_empty() {
+            ensure!(
+                self.callback_allowed_hosts
+                    .iter()
+                    .any(|allowed| allowed == host),
+                "callback URL host {host} is not allowed"
+            );
+            return Ok(());
+        }
+        // IPv6 hosts are in brackets.
+        if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            ensure!(
+                is_public_ip(ip),
+                "callback URL must not point to a non-public address"
+            );
+        }
+        Ok(())
+    }
+
+    async fn notify(&self, callback_url: &str, job_response: &JobResponse) {
+        match self
+            .http_client
+            .post(callback_url)
+            .json(job_response)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            Ok(_) => info!(job_id = job_response.job_id, "Notified job callback URL"),
+            Err(e) => warn!(
+                job_id = job_response.job_id,
+                error = e.to_string(),
+                "Failed to notify job callback URL"
+            ),
+        }
+    }
+}
+
+/// Periodically drop the finished jobs that have expired, so that jobs that are never polled
+/// again do not pile up.
+pub fn spawn_eviction_loop(state: Arc<ProverServiceState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            state.jobs.evict_expired();
+        }
+    })
+}
+
+/// Whether `ip` is reachable on the public internet, as opposed to, e.g., a loopback, private,
+/// link-local (which includes cloud metadata endpoints) or otherwise reserved address.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // "this network", shared address space (carrier-grade NAT), benchmarking and
+                // reserved ranges
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (18..20).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first_segment = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    // unique local and link-local
+                    || (first_segment & 0xfe00) == 0xfc00
+                    || (first_segment & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves host names like the system resolver, but only returns public addresses.
+struct PublicAddrResolver;
+
+impl Resolve for PublicAddrResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
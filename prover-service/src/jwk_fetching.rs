@@ -1,14 +1,25 @@
 // Copyright © Aptos Foundation
 
-use crate::config::OidcProvider;
-use anyhow::{anyhow, Result};
+use crate::config::{IssuerPattern, OidcProvider, ProverServiceConfig};
+use crate::metrics;
+use anyhow::{anyhow, ensure, Result};
 use aptos_keyless_common::input_processing::encoding::DecodedJWT;
 use aptos_types::jwks::rsa::RSA_JWK;
 use dashmap::DashMap;
+use lru::LruCache;
 use once_cell::sync::Lazy;
-use regex::Regex;
+use reqwest::{
+    header::{HeaderValue, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde::Deserialize;
 use serde_json::Value;
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 
 pub type Issuer = String;
@@ -17,32 +28,198 @@ pub type KeyID = String;
 // TODO: this is a duplicate of the jwk fetching in the pepper service, with changes b/c the
 // DecodingKey type that the pepper service uses is too opaque to use here. We should unify.
 
-static AUTH_0_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"^https://[a-zA-Z0-9-_]+\.us\.auth0\.com/$").unwrap());
-
-static COGNITO_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^https://cognito-idp\.[a-zA-Z0-9-_]+\.amazonaws\.com/[a-zA-Z0-9-_]+$").unwrap()
-});
-
 /// The JWK in-mem cache.
 pub static DECODING_KEY_CACHE: Lazy<DashMap<Issuer, DashMap<KeyID, Arc<RSA_JWK>>>> =
     Lazy::new(DashMap::new);
 
-pub async fn get_federated_jwk(jwt: &DecodedJWT) -> Result<Arc<RSA_JWK>> {
-    let jwk_url = if AUTH_0_REGEX.is_match(&jwt.payload.iss) {
-        format!("{}.well-known/jwks.json", jwt.payload.iss)
-    } else if COGNITO_REGEX.is_match(&jwt.payload.iss) {
-        format!("{}/.well-known/jwks.json", jwt.payload.iss)
-    } else {
+/// How long the discovered JWK endpoint and the JWKs of a federated issuer are cached for.
+const FEDERATED_JWKS_TTL: Duration = Duration::from_secs(300);
+/// How often the JWKs of a federated issuer may be fetched again early, for a JWT whose `kid` is
+/// not among the cached ones, or after a failed discovery or fetch.
+const FEDERATED_JWKS_MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+/// How many federated issuers are cached at most. Anyone can mint JWTs for issuers that match a
+/// pattern, so the least recently used ones are dropped past this.
+const FEDERATED_JWKS_CACHE_CAPACITY: usize = 1_000;
+
+/// The outcome of the last discovery and JWKs fetch for a federated issuer.
+struct FederatedJwks {
+    /// Where the JWKs were fetched from, if discovery succeeded.
+    jwks_uri: Option<String>,
+    keys: DashMap<KeyID, Arc<RSA_JWK>>,
+    /// When `keys` were fetched, if they ever were.
+    fetched_at: Option<Instant>,
+    /// When discovery or a fetch was last attempted, and why it failed, if it did.
+    attempted_at: Instant,
+    error: Option<String>,
+}
+
+impl FederatedJwks {
+    /// Fetched JWKs expire after the TTL. Failures are only cached for the min refetch interval.
+    fn is_expired(&self) -> bool {
+        match self.fetched_at {
+            Some(fetched_at) => fetched_at.elapsed() >= FEDERATED_JWKS_TTL,
+            None => self.attempted_at.elapsed() >= FEDERATED_JWKS_MIN_REFETCH_INTERVAL,
+        }
+    }
+}
+
+/// The federated issuers whose JWKs were fetched, or failed to be fetched, recently.
+static FEDERATED_JWKS_CACHE: Lazy<Mutex<LruCache<Issuer, Arc<FederatedJwks>>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(FEDERATED_JWKS_CACHE_CAPACITY)));
+
+/// Fetch the JWK for `jwt` from its issuer, which must match one of `federated_issuer_patterns`.
+/// The issuer's JWK endpoint is discovered, and its JWKs are cached for a while, so that requests
+/// with JWTs from the same issuer do not each cost two outbound requests. So are failures, so
+/// that JWTs from a broken or malicious issuer do not either.
+pub async fn get_federated_jwk(
+    federated_issuer_patterns: &[IssuerPattern],
+    jwt: &DecodedJWT,
+) -> Result<Arc<RSA_JWK>> {
+    let iss = &jwt.payload.iss;
+    if !is_federated_issuer(federated_issuer_patterns, iss) {
         return Err(anyhow!("not a federated iss"));
+    }
+
+    let jwks = match cached_federated_jwks(iss) {
+        Some(cached)
+            if cached.keys.contains_key(&jwt.header.kid)
+                || cached.attempted_at.elapsed() < FEDERATED_JWKS_MIN_REFETCH_INTERVAL =>
+        {
+            cached
+        }
+        cached => {
+            let jwks = Arc::new(
+                fetch_federated_jwks(federated_issuer_patterns, iss, cached.as_deref()).await,
+            );
+            cache_federated_jwks(iss, jwks.clone());
+            jwks
+        }
     };
 
-    let keys = fetch_jwks(&jwk_url).await?;
+    if let Some(key) = jwks.keys.get(&jwt.header.kid) {
+        return Ok(key.clone());
+    }
+    match &jwks.error {
+        Some(error) => Err(anyhow!("failed to fetch the JWKs of {iss}: {error}")),
+        None => Err(anyhow!("unknown kid: {}", jwt.header.kid)),
+    }
+}
+
+/// Discover (unless `previous` already did) and fetch the JWKs of federated issuer `iss`. On
+/// failure, the JWKs of `previous` are kept until they expire.
+async fn fetch_federated_jwks(
+    federated_issuer_patterns: &[IssuerPattern],
+    iss: &str,
+    previous: Option<&FederatedJwks>,
+) -> FederatedJwks {
+    let mut jwks_uri = previous.and_then(|previous| previous.jwks_uri.clone());
+    let result = async {
+        let uri = match &jwks_uri {
+            Some(uri) => uri.clone(),
+            None => discover_jwks_uri(&FEDERATED_HTTP_CLIENT, iss).await?,
+        };
+        // Checked before every fetch, since the patterns may have been reloaded.
+        check_federated_jwks_uri(federated_issuer_patterns, iss, &uri)?;
+        jwks_uri = Some(uri.clone());
+        fetch_jwks_with(&FEDERATED_HTTP_CLIENT, &uri).await
+    }
+    .await;
 
-    let key = keys
-        .get(&jwt.header.kid)
-        .ok_or_else(|| anyhow!("unknown kid: {}", jwt.header.kid))?;
-    Ok(key.clone())
+    match result {
+        Ok(keys) => FederatedJwks {
+            jwks_uri,
+            keys,
+            fetched_at: Some(Instant::now()),
+            attempted_at: Instant::now(),
+            error: None,
+        },
+        Err(e) => {
+            warn!("Failed to fetch the JWKs of federated issuer {iss}: {e}");
+            FederatedJwks {
+                jwks_uri,
+                keys: previous
+                    .map(|previous| previous.keys.clone())
+                    .unwrap_or_default(),
+                fetched_at: previous.and_then(|previous| previous.fetched_at),
+                attempted_at: Instant::now(),
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+fn cached_federated_jwks(iss: &str) -> Option<Arc<FederatedJwks>> {
+    let mut cache = FEDERATED_JWKS_CACHE.lock().unwrap();
+    let jwks = cache.get(iss)?.clone();
+    if jwks.is_expired() {
+        cache.pop(iss);
+        return None;
+    }
+    Some(jwks)
+}
+
+fn cache_federated_jwks(iss: &str, jwks: Arc<FederatedJwks>) {
+    let mut cache = FEDERATED_JWKS_CACHE.lock().unwrap();
+    // Issuers that are not used anymore end up least recently used. Drop those that expired.
+    while cache.peek_lru().is_some_and(|(_, jwks)| jwks.is_expired()) {
+        cache.pop_lru();
+    }
+    cache.put(String::from(iss), jwks);
+}
+
+/// Whether one of `patterns` matches the whole of `iss`.
+pub fn is_federated_issuer(patterns: &[IssuerPattern], iss: &str) -> bool {
+    patterns.iter().any(|pattern| pattern.is_match(iss))
+}
+
+/// Check that the `jwks_uri` that federated issuer `iss` discovered is safe to fetch: it must be
+/// on the issuer's own HTTPS origin, or match one of `patterns`. Otherwise, a JWT with a
+/// matching `iss` could make the service fetch any URL, e.g., on the internal network.
+pub fn check_federated_jwks_uri(
+    patterns: &[IssuerPattern],
+    iss: &str,
+    jwks_uri: &str,
+) -> Result<()> {
+    if patterns.iter().any(|pattern| pattern.is_match(jwks_uri)) {
+        return Ok(());
+    }
+    let iss_url = reqwest::Url::parse(iss).map_err(|e| anyhow!("invalid iss {iss}: {e}"))?;
+    let jwks_url =
+        reqwest::Url::parse(jwks_uri).map_err(|e| anyhow!("invalid jwks_uri {jwks_uri}: {e}"))?;
+    ensure!(
+        jwks_url.scheme() == "https" && jwks_url.origin() == iss_url.origin(),
+        "jwks_uri {jwks_uri} is not on the HTTPS origin of {iss}"
+    );
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct OpenIdConfiguration {
+    issuer: String,
+    jwks_uri: String,
+}
+
+/// The `jwks_uri` of `iss`'s OIDC discovery document, which must be for `iss` itself.
+pub async fn discover_jwks_uri(client: &reqwest::Client, iss: &str) -> Result<String> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        iss.trim_end_matches('/')
+    );
+    let configuration: OpenIdConfiguration = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| anyhow!("OIDC discovery error: {}", e))?
+        .json()
+        .await
+        .map_err(|e| anyhow!("error while parsing {url}: {}", e))?;
+    ensure!(
+        configuration.issuer == iss,
+        "{url} is for issuer {}, not {iss}",
+        configuration.issuer
+    );
+    Ok(configuration.jwks_uri)
 }
 
 pub async fn fetch_jwk(jwt: &DecodedJWT, jwk_url: &str) -> Result<Arc<RSA_JWK>> {
@@ -53,11 +230,43 @@ pub async fn fetch_jwk(jwt: &DecodedJWT, jwk_url: &str) -> Result<Arc<RSA_JWK>>
     Ok(key.clone())
 }
 
+/// Client for one-off JWK fetches.
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap()
+});
+
+/// Client for the discovery and JWKs of federated issuers. It does not follow redirects, which
+/// would get around [`check_federated_jwks_uri`].
+static FEDERATED_HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+});
+
 /// Send a request to a JWK endpoint and return its JWK map.
 pub async fn fetch_jwks(jwk_url: &str) -> Result<DashMap<KeyID, Arc<RSA_JWK>>> {
-    let response = reqwest::get(jwk_url)
+    fetch_jwks_with(&HTTP_CLIENT, jwk_url).await
+}
+
+async fn fetch_jwks_with(
+    client: &reqwest::Client,
+    jwk_url: &str,
+) -> Result<DashMap<KeyID, Arc<RSA_JWK>>> {
+    let response = client
+        .get(jwk_url)
+        .send()
         .await
+        .and_then(reqwest::Response::error_for_status)
         .map_err(|e| anyhow!("jwk fetch error: {}", e))?;
+    parse_jwks(response).await
+}
+
+async fn parse_jwks(response: reqwest::Response) -> Result<DashMap<KeyID, Arc<RSA_JWK>>> {
     let text = response
         .text()
         .await
@@ -72,15 +281,8 @@ pub async fn fetch_jwks(jwk_url: &str) -> Result<DashMap<KeyID, Arc<RSA_JWK>>> {
         .ok_or_else(|| anyhow!("Error while parsing jwk json: \"keys\" not array"))?;
     let key_map: DashMap<KeyID, Arc<RSA_JWK>> = keys
         .iter()
-        .filter_map(|jwk_val| match RSA_JWK::try_from(jwk_val) {
-            Ok(jwk) => {
-                if jwk.e == "AQAB" {
-                    Some((jwk.kid.clone(), Arc::new(jwk)))
-                } else {
-                    warn!("Unsupported RSA modulus for jwk: {}", jwk_val);
-                    None
-                }
-            }
+        .filter_map(|jwk_val| match parse_jwk(jwk_val) {
+            Ok(jwk) => Some((jwk.kid.clone(), Arc::new(jwk))),
             Err(e) => {
                 warn!("error while parsing jwk {}: {e}", jwk_val);
                 None
@@ -90,32 +292,15 @@ pub async fn fetch_jwks(jwk_url: &str) -> Result<DashMap<KeyID, Arc<RSA_JWK>>> {
     Ok(key_map)
 }
 
-pub async fn populate_jwk_cache(issuer: &str, jwk_url: &str) {
-    fetch_and_cache_jwk(issuer, jwk_url).await;
-}
-
-pub fn start_jwk_refresh_loop(issuer: &str, jwk_url: &str, refresh_interval: Duration) {
-    let issuer = issuer.to_string();
-    let jwk_url = jwk_url.to_string();
-    let _handle = tokio::spawn(async move {
-        loop {
-            fetch_and_cache_jwk(&issuer, &jwk_url).await;
-            tokio::time::sleep(refresh_interval).await;
-        }
-    });
-}
-
-async fn fetch_and_cache_jwk(issuer: &str, jwk_url: &str) {
-    match fetch_jwks(jwk_url).await {
-        Ok(key_set) => {
-            let num_keys = key_set.len();
-            info!(num_keys, issuer, "Updated key set",);
-            DECODING_KEY_CACHE.insert(issuer.to_string(), key_set);
-        }
-        Err(msg) => {
-            error!("{}", msg);
-        }
+/// Parse an RSA JWK. Its `alg` is kept, so that JWTs can be checked against it; a JWK without one
+/// gets an empty `alg`. Keys with any public exponent are kept, although the circuit only supports
+/// 65537: see [`crate::training_wheels::verification_logic::circuit_supports`].
+pub fn parse_jwk(jwk_val: &Value) -> Result<RSA_JWK> {
+    let mut jwk_val = jwk_val.clone();
+    if let Some(fields) = jwk_val.as_object_mut() {
+        fields.entry("alg").or_insert_with(|| Value::from(""));
     }
+    RSA_JWK::try_from(&jwk_val)
 }
 
 pub fn cached_decoding_key(issuer: &str, kid: &str) -> Result<Arc<RSA_JWK>> {
@@ -128,15 +313,315 @@ pub fn cached_decoding_key(issuer: &str, kid: &str) -> Result<Arc<RSA_JWK>> {
     Ok(key.clone())
 }
 
-pub async fn init_jwk_fetching(oidc_providers: &Vec<OidcProvider>, jwk_refresh_rate: Duration) {
+/// How the JWKs of configured providers are refreshed; see the `jwk_*` fields of
+/// [`ProverServiceConfig`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JwkFetchSettings {
+    pub refresh_interval: Duration,
+    pub max_refresh_interval: Duration,
+    pub max_backoff: Duration,
+    pub grace_period: Duration,
+}
+
+impl JwkFetchSettings {
+    pub fn from_config(config: &ProverServiceConfig) -> Self {
+        Self {
+            refresh_interval: Duration::from_secs(config.jwk_refresh_rate_secs),
+            max_refresh_interval: Duration::from_secs(config.jwk_max_refresh_interval_secs),
+            max_backoff: Duration::from_secs(config.jwk_max_backoff_secs),
+            grace_period: Duration::from_secs(config.jwk_grace_period_secs),
+        }
+    }
+
+    /// How long to wait after a successful fetch whose response had this `max-age`.
+    pub fn refresh_delay(&self, max_age: Option<Duration>) -> Duration {
+        max_age.map_or(self.refresh_interval, |max_age| {
+            max_age
+                .min(self.max_refresh_interval)
+                .max(self.refresh_interval)
+        })
+    }
+
+    /// How long to wait after `consecutive_failures` failed fetches in a row.
+    pub fn backoff_delay(&self, consecutive_failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(consecutive_failures.saturating_sub(1));
+        self.refresh_interval
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// The `max-age` directive of a `Cache-Control` header, unless caching is disallowed.
+pub fn cache_control_max_age(cache_control: &str) -> Option<Duration> {
+    let directives: Vec<&str> = cache_control.split(',').map(str::trim).collect();
+    if directives.iter().any(|directive| {
+        directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store")
+    }) {
+        return None;
+    }
+    directives.iter().find_map(|directive| {
+        let (name, value) = directive.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("max-age") {
+            return None;
+        }
+        value.trim().parse().ok().map(Duration::from_secs)
+    })
+}
+
+/// When the JWKs of each configured provider were last known to be up to date.
+static LAST_FETCHED: Lazy<DashMap<Issuer, Instant>> = Lazy::new(DashMap::new);
+
+/// Set the JWK cache age metric of every configured provider. Call before gathering metrics.
+pub fn update_jwk_age_metrics() {
+    for last_fetched in LAST_FETCHED.iter() {
+        metrics::JWK_CACHE_AGE_SECS
+            .with_label_values(&[last_fetched.key().as_str()])
+            .set(last_fetched.value().elapsed().as_secs() as i64);
+    }
+}
+
+/// Keeps the cached JWKs of one provider up to date.
+struct JwkFetcher {
+    issuer: Issuer,
+    /// The configured `endpoint_url`, if any.
+    configured_jwk_url: Option<String>,
+    /// `configured_jwk_url`, or the discovered one once discovery succeeded.
+    jwk_url: Option<String>,
+    client: reqwest::Client,
+    settings: JwkFetchSettings,
+    /// The `ETag` and `Last-Modified` of the last response, to make conditional requests with.
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    /// Keys that the provider stopped serving, and since when.
+    retired_keys: HashMap<KeyID, (Arc<RSA_JWK>, Instant)>,
+    consecutive_failures: u32,
+}
+
+enum JwksResponse {
+    Modified {
+        keys: DashMap<KeyID, Arc<RSA_JWK>>,
+        max_age: Option<Duration>,
+    },
+    NotModified {
+        max_age: Option<Duration>,
+    },
+}
+
+impl JwkFetcher {
+    fn new(provider: &OidcProvider, settings: JwkFetchSettings) -> Result<Self> {
+        Ok(Self {
+            issuer: provider.iss.clone(),
+            configured_jwk_url: provider.endpoint_url.clone(),
+            jwk_url: provider.endpoint_url.clone(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(provider.fetch_timeout_secs))
+                .build()?,
+            settings,
+            etag: None,
+            last_modified: None,
+            retired_keys: HashMap::new(),
+            consecutive_failures: 0,
+        })
+    }
+
+    /// Fetch the JWKs and cache them, keeping the cached ones if this fails. Returns how long to
+    /// wait before the next fetch.
+    async fn refresh(&mut self) -> Duration {
+        let issuer = self.issuer.clone();
+        let delay = match self.fetch().await {
+            Ok(JwksResponse::Modified { keys, max_age }) => {
+                info!(num_keys = keys.len(), issuer = %issuer, "Updated key set");
+                self.cache(keys);
+                self.fetched("updated");
+                self.settings.refresh_delay(max_age)
+            }
+            Ok(JwksResponse::NotModified { max_age }) => {
+                self.fetched("not_modified");
+                self.settings.refresh_delay(max_age)
+            }
+            Err(e) => {
+                self.consecutive_failures += 1;
+                if self.configured_jwk_url.is_none() {
+                    // The JWKs may have moved; discover them again on the next try.
+                    self.jwk_url = None;
+                }
+                metrics::JWK_FETCHES
+                    .with_label_values(&[issuer.as_str(), "failed"])
+                    .inc();
+                let delay = self.settings.backoff_delay(self.consecutive_failures);
+                error!(
+                    issuer = %issuer,
+                    consecutive_failures = self.consecutive_failures,
+                    "JWK fetch failed, retrying in {delay:?}: {e}"
+                );
+                delay
+            }
+        };
+        self.expire_retired_keys();
+        delay
+    }
+
+    async fn fetch(&mut self) -> Result<JwksResponse> {
+        let jwk_url = match &self.jwk_url {
+            Some(jwk_url) => jwk_url.clone(),
+            None => {
+                let jwk_url = discover_jwks_uri(&self.client, &self.issuer).await?;
+                info!(issuer = %self.issuer, jwk_url = %jwk_url, "Discovered JWK endpoint");
+                self.jwk_url = Some(jwk_url.clone());
+                self.etag = None;
+                self.last_modified = None;
+                jwk_url
+            }
+        };
+        let mut request = self.client.get(&jwk_url);
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| anyhow!("jwk fetch error: {}", e))?;
+
+        let max_age = response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok())
+            .and_then(cache_control_max_age);
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(JwksResponse::NotModified { max_age });
+        }
+        let etag = response.headers().get(ETAG).cloned();
+        let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+        let keys = parse_jwks(response).await?;
+        // Only remember the validators of responses that parsed, so that a bad response is not
+        // answered with "not modified" from then on.
+        self.etag = etag;
+        self.last_modified = last_modified;
+        Ok(JwksResponse::Modified { keys, max_age })
+    }
+
+    fn fetched(&mut self, outcome: &str) {
+        self.consecutive_failures = 0;
+        LAST_FETCHED.insert(self.issuer.clone(), Instant::now());
+        metrics::JWK_FETCHES
+            .with_label_values(&[self.issuer.as_str(), outcome])
+            .inc();
+    }
+
+    /// Replace the cached keys with `keys`, plus those that were just retired or are still
+    /// within their grace period.
+    fn cache(&mut self, keys: DashMap<KeyID, Arc<RSA_JWK>>) {
+        let now = Instant::now();
+        if let Some(cached_keys) = DECODING_KEY_CACHE.get(&self.issuer) {
+            for cached_key in cached_keys.iter() {
+                if !keys.contains_key(cached_key.key()) {
+                    self.retired_keys
+                        .entry(cached_key.key().clone())
+                        .or_insert_with(|| (cached_key.value().clone(), now));
+                }
+            }
+        }
+        self.retired_keys.retain(|kid, _| !keys.contains_key(kid));
+        for (kid, (jwk, _)) in &self.retired_keys {
+            keys.insert(kid.clone(), jwk.clone());
+        }
+        DECODING_KEY_CACHE.insert(self.issuer.clone(), keys);
+    }
+
+    /// Stop accepting the retired keys whose grace period is over.
+    fn expire_retired_keys(&mut self) {
+        let grace_period = self.settings.grace_period;
+        let expired: Vec<KeyID> = self
+            .retired_keys
+            .iter()
+            .filter(|(_, (_, retired_at))| retired_at.elapsed() >= grace_period)
+            .map(|(kid, _)| kid.clone())
+            .collect();
+        for kid in expired {
+            info!(issuer = %self.issuer, kid = %kid, "Dropped retired JWK");
+            self.retired_keys.remove(&kid);
+            if let Some(cached_keys) = DECODING_KEY_CACHE.get(&self.issuer) {
+                cached_keys.remove(&kid);
+            }
+        }
+    }
+}
+
+pub async fn init_jwk_fetching(oidc_providers: &[OidcProvider], settings: JwkFetchSettings) {
     info!("current cache: {:?}", DECODING_KEY_CACHE);
+    update_jwk_fetching(oidc_providers, settings).await;
+}
+
+struct RefreshLoop {
+    provider: OidcProvider,
+    settings: JwkFetchSettings,
+    handle: JoinHandle<()>,
+}
+
+/// The JWK refresh loop of every configured provider, by issuer.
+static REFRESH_LOOPS: Lazy<Mutex<HashMap<Issuer, RefreshLoop>>> = Lazy::new(Default::default);
 
-    for provider in oidc_providers {
+/// Make the JWK refresh loops match a (possibly reloaded) config: stop the loops of providers that
+/// were removed, forgetting their JWKs, and (re)start those of providers that were added or
+/// changed.
+pub async fn update_jwk_fetching(oidc_providers: &[OidcProvider], settings: JwkFetchSettings) {
+    let new_providers: Vec<OidcProvider> = {
+        let mut refresh_loops = REFRESH_LOOPS.lock().unwrap();
+        refresh_loops.retain(|issuer, refresh_loop| {
+            let unchanged = oidc_providers.contains(&refresh_loop.provider)
+                && refresh_loop.settings == settings;
+            if !unchanged {
+                refresh_loop.handle.abort();
+                if !oidc_providers
+                    .iter()
+                    .any(|provider| &provider.iss == issuer)
+                {
+                    info!(issuer, "Stopped fetching JWKs");
+                    DECODING_KEY_CACHE.remove(issuer);
+                    LAST_FETCHED.remove(issuer);
+                    let _ = metrics::JWK_CACHE_AGE_SECS.remove_label_values(&[issuer.as_str()]);
+                }
+            }
+            unchanged
+        });
+        oidc_providers
+            .iter()
+            .filter(|provider| !refresh_loops.contains_key(&provider.iss))
+            .cloned()
+            .collect()
+    };
+
+    for provider in new_providers {
+        let mut fetcher = match JwkFetcher::new(&provider, settings) {
+            Ok(fetcher) => fetcher,
+            Err(e) => {
+                error!(issuer = %provider.iss, "Couldn't start fetching JWKs: {e}");
+                continue;
+            }
+        };
         // Do initial jwk cache population non-async, so that we don't handle requests before this is
         // populated
-        populate_jwk_cache(&provider.iss, &provider.endpoint_url).await;
+        let first_delay = fetcher.refresh().await;
 
         // init jwk polling job for this provider
-        start_jwk_refresh_loop(&provider.iss, &provider.endpoint_url, jwk_refresh_rate);
+        let handle = tokio::spawn(async move {
+            let mut delay = first_delay;
+            loop {
+                tokio::time::sleep(delay).await;
+                delay = fetcher.refresh().await;
+            }
+        });
+        REFRESH_LOOPS.lock().unwrap().insert(
+            provider.iss.clone(),
+            RefreshLoop {
+                provider,
+                settings,
+                handle,
+            },
+        );
     }
 }
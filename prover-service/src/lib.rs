@@ -3,18 +3,28 @@
 extern crate core;
 
 pub mod api;
+pub mod auth;
 pub mod config;
+pub mod config_reload;
 pub mod error;
 pub mod groth16_vk;
 pub mod handlers;
+pub mod health;
 pub mod input_processing;
+pub mod jobs;
 pub mod jwk_fetching;
 pub mod load_vk;
 pub mod logging;
 pub mod metrics;
+pub mod proof_cache;
 pub mod prover_key;
+pub mod prover_pool;
 pub mod proving;
+pub mod rate_limit;
+pub mod setup;
+pub mod shutdown;
 pub mod state;
+pub mod tls;
 pub mod training_wheels;
 pub mod watcher;
 pub mod witness_gen;
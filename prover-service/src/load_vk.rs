@@ -1,5 +1,6 @@
 // Copyright © Aptos Foundation
 
+use anyhow::{Context, Result};
 use aptos_types::keyless::{g1_projective_str_to_affine, g2_projective_str_to_affine};
 use ark_bn254::Bn254;
 use ark_groth16::{PreparedVerifyingKey, VerifyingKey};
@@ -18,34 +19,37 @@ struct RawVK {
 
 /// This function uses the decimal uncompressed point serialization which is outputted by circom.
 pub fn prepared_vk(vk_file_path: &str) -> PreparedVerifyingKey<Bn254> {
-    let raw_vk: RawVK =
-        serde_yaml::from_str(&fs::read_to_string(vk_file_path).expect("Unable to read file"))
-            .expect("should parse correctly");
+    try_prepared_vk(vk_file_path).expect("should parse correctly")
+}
+
+/// Like [`prepared_vk`], but fails instead of panicking if the file is missing or malformed.
+pub fn try_prepared_vk(vk_file_path: &str) -> Result<PreparedVerifyingKey<Bn254>> {
+    let raw_vk: RawVK = serde_yaml::from_str(
+        &fs::read_to_string(vk_file_path)
+            .with_context(|| format!("failed to read {vk_file_path}"))?,
+    )
+    .with_context(|| format!("failed to parse {vk_file_path}"))?;
 
-    let alpha_g1 =
-        g1_projective_str_to_affine(&raw_vk.vk_alpha_1[0], &raw_vk.vk_alpha_1[1]).unwrap();
+    let alpha_g1 = g1_projective_str_to_affine(&raw_vk.vk_alpha_1[0], &raw_vk.vk_alpha_1[1])?;
 
     let beta_g2 = g2_projective_str_to_affine(
         [&raw_vk.vk_beta_2[0][0], &raw_vk.vk_beta_2[0][1]],
         [&raw_vk.vk_beta_2[1][0], &raw_vk.vk_beta_2[1][1]],
-    )
-    .unwrap();
+    )?;
 
     let gamma_g2 = g2_projective_str_to_affine(
         [&raw_vk.vk_gamma_2[0][0], &raw_vk.vk_gamma_2[0][1]],
         [&raw_vk.vk_gamma_2[1][0], &raw_vk.vk_gamma_2[1][1]],
-    )
-    .unwrap();
+    )?;
 
     let delta_g2 = g2_projective_str_to_affine(
         [&raw_vk.vk_delta_2[0][0], &raw_vk.vk_delta_2[0][1]],
         [&raw_vk.vk_delta_2[1][0], &raw_vk.vk_delta_2[1][1]],
-    )
-    .unwrap();
+    )?;
 
     let mut gamma_abc_g1 = Vec::new();
     for p in raw_vk.IC {
-        gamma_abc_g1.push(g1_projective_str_to_affine(&p[0], &p[1]).unwrap());
+        gamma_abc_g1.push(g1_projective_str_to_affine(&p[0], &p[1])?);
     }
 
     let vk = VerifyingKey {
@@ -56,5 +60,5 @@ pub fn prepared_vk(vk_file_path: &str) -> PreparedVerifyingKey<Bn254> {
         gamma_abc_g1,
     };
 
-    PreparedVerifyingKey::from(vk)
+    Ok(PreparedVerifyingKey::from(vk))
 }
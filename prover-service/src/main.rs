@@ -2,8 +2,9 @@
 
 use axum::{
     http::header,
+    middleware,
     routing::{get, post},
-    Json, Router,
+    Router,
 };
 use http::{Method, StatusCode};
 use log::info;
@@ -16,11 +17,16 @@ use axum_prometheus::{
     utils::SECONDS_DURATION_BUCKETS,
     PrometheusMetricLayerBuilder, AXUM_HTTP_REQUESTS_DURATION_SECONDS,
 };
+use axum_server::tls_rustls::RustlsConfig;
 use prover_service::config::CONFIG;
+use prover_service::jwk_fetching::JwkFetchSettings;
 use std::{fs, net::SocketAddr, sync::Arc, time::Duration};
 use tower::ServiceBuilder;
 use tower_http::cors::{Any, CorsLayer};
 
+/// After draining, how long to wait for responses to be written before closing connections.
+const SERVER_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() {
     let cors = CorsLayer::new()
@@ -37,17 +43,27 @@ async fn main() {
     let state = ProverServiceState::init();
     let state = Arc::new(state);
 
-    let vkey = fs::read_to_string(state.config.verification_key_path())
+    let vkey = fs::read_to_string(state.config().verification_key_path())
         .expect("Unable to read default vkey file");
     info!("Default verifying Key: {}", vkey);
 
-    // init jwk fetching job; refresh every `config.jwk_refresh_rate_secs` seconds
+    rate_limit::spawn_eviction_loop(state.clone());
+    jobs::spawn_eviction_loop(state.clone());
+
+    if let Some(poll_secs) = CONFIG.setup_reload_poll_secs {
+        setup::spawn_reload_loops(state.clone(), Duration::from_secs(poll_secs.max(1)));
+    }
+
+    // init jwk fetching job; refresh every `config.jwk_refresh_rate_secs` seconds or so
     jwk_fetching::init_jwk_fetching(
         &CONFIG.oidc_providers,
-        Duration::from_secs(CONFIG.jwk_refresh_rate_secs),
+        JwkFetchSettings::from_config(&CONFIG),
     )
     .await;
 
+    // re-read the config file and the training wheels keys on SIGHUP
+    config_reload::spawn_sighup_reload_loop(state.clone());
+
     let (prometheus_layer, metric_handle) = PrometheusMetricLayerBuilder::new()
         .with_prefix("prover")
         .enable_response_body_size(true)
@@ -63,31 +79,93 @@ async fn main() {
         })
         .build_pair();
 
-    // init axum and serve public routes
-    let app = Router::new()
-        .route("/meta", get((StatusCode::OK, Json(CONFIG.clone()))))
+    // routes that end up in the prover queue, and are hence rate limited per client IP
+    let prove_routes = Router::new()
         .route(
             "/v0/prove",
             post(handlers::prove_handler).fallback(handlers::fallback_handler),
         )
+        .route(
+            "/v0/prove_batch",
+            post(handlers::prove_batch_handler).fallback(handlers::fallback_handler),
+        )
+        .route("/v1/jobs", post(handlers::create_job_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_by_client_ip,
+        ));
+
+    // routes that need an API key and a client certificate, if any are configured
+    let authenticated_routes = Router::new()
+        .merge(prove_routes)
+        .route(
+            "/v0/validate",
+            post(handlers::validate_handler).fallback(handlers::fallback_handler),
+        )
+        .route("/v1/jobs/:id", get(handlers::get_job_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            auth::ClientCertificatePolicy::from_config(&CONFIG),
+            auth::require_client_certificate,
+        ));
+
+    // init axum and serve public routes
+    let app = Router::new()
+        .route("/meta", get(handlers::meta_handler))
+        .merge(authenticated_routes)
         .route("/healthcheck", get(handlers::healthcheck_handler))
+        .route("/readyz", get(handlers::readyz_handler))
+        .route("/livez", get(handlers::livez_handler))
         .fallback(handlers::fallback_handler)
         .with_state(state.clone())
         .layer(ServiceBuilder::new().layer(cors))
         .layer(prometheus_layer);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], CONFIG.port));
-    let app_handle = tokio::spawn(async move {
-        let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-        axum::serve(listener, app).await.unwrap();
-    });
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server_handle = axum_server::Handle::new();
+    let mut app_handle = match &CONFIG.tls {
+        Some(tls_config) => {
+            let rustls_config = tls::rustls_server_config(tls_config)
+                .expect("Couldn't load TLS certificate and key");
+            info!(
+                "Serving over TLS; client certificates required for proving: {}",
+                tls_config.client_ca_path.is_some()
+            );
+            let rustls_config = RustlsConfig::from_config(Arc::new(rustls_config));
+            tls::spawn_reload_loop(tls_config.clone(), rustls_config.clone());
+            let server_handle = server_handle.clone();
+            tokio::spawn(async move {
+                axum_server::bind(addr)
+                    .acceptor(tls::ClientCertAcceptor::new(rustls_config))
+                    .handle(server_handle)
+                    .serve(app)
+                    .await
+                    .unwrap();
+            })
+        }
+        None => {
+            let server_handle = server_handle.clone();
+            tokio::spawn(async move {
+                axum_server::bind(addr)
+                    .handle(server_handle)
+                    .serve(app)
+                    .await
+                    .unwrap();
+            })
+        }
+    };
 
-    // serve metrics on metrics_port; this is so that we don't have to expose metrics route publicly
+    // serve metrics on metrics_port; this is so that we don't have to expose them publicly
     let app_metrics = Router::new()
         .route(
             "/metrics",
             get(|| async move {
                 // TODO: will this pick up metrics from the `metric_handle`?
+                jwk_fetching::update_jwk_age_metrics();
                 let metrics = prometheus::gather();
 
                 let mut encode_buffer = vec![];
@@ -112,15 +190,42 @@ async fn main() {
         .fallback(handlers::fallback_handler);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], CONFIG.metrics_port));
-    let metrics_handle = tokio::spawn(async move {
+    let mut metrics_handle = tokio::spawn(async move {
         let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
         axum::serve(listener, app_metrics).await.unwrap();
     });
 
-    // Wait for both serve jobs to finish indefinitely, or until one of them panics
-    let res = tokio::try_join!(app_handle, metrics_handle);
-    panic!(
-        "One of the tasks that weren't meant to end ended unexpectedly: {:?}",
-        res
-    );
+    // serve admin routes on admin_port, on localhost only, since they are unauthenticated
+    let app_admin = Router::new()
+        .route("/admin/reload_setup", post(handlers::reload_setup_handler))
+        .route(
+            "/admin/reload_config",
+            post(handlers::reload_config_handler),
+        )
+        .fallback(handlers::fallback_handler)
+        .with_state(state.clone());
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], CONFIG.admin_port));
+    let mut admin_handle = tokio::spawn(async move {
+        let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+        axum::serve(listener, app_admin).await.unwrap();
+    });
+
+    // Serve until we are told to shut down, or until one of the serve jobs panics
+    tokio::select! {
+        res = async { tokio::try_join!(&mut app_handle, &mut metrics_handle, &mut admin_handle) } => panic!(
+            "One of the tasks that weren't meant to end ended unexpectedly: {:?}",
+            res
+        ),
+        () = shutdown::signal_received() => {}
+    }
+
+    // Keep serving while draining, so that in-flight proofs can still be returned to clients.
+    info!("Shutting down; no longer admitting prove requests");
+    let drain_timeout = Duration::from_secs(CONFIG.shutdown_drain_timeout_secs);
+    shutdown::drain(&state, drain_timeout).await;
+
+    server_handle.graceful_shutdown(Some(SERVER_SHUTDOWN_GRACE_PERIOD));
+    let _ = app_handle.await;
+    info!("Shut down cleanly");
 }
@@ -1,7 +1,10 @@
 // Copyright © Aptos Foundation
 
 use once_cell::sync::Lazy;
-use prometheus::{register_histogram, Histogram};
+use prometheus::{
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
 
 pub static PROVER_TIME_SECS: Lazy<Histogram> =
     Lazy::new(|| register_histogram!("prover_time_secs", "Prover time in seconds",).unwrap());
@@ -32,3 +35,108 @@ pub static REQUEST_QUEUE_TIME_SECS: Lazy<Histogram> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+pub static PROVER_WORKER_PROOFS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_worker_proofs_total",
+        "Number of proofs computed by each prover worker",
+        &["setup", "vk_hash", "worker"]
+    )
+    .unwrap()
+});
+
+pub static PROVER_WORKER_BUSY: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "prover_worker_busy",
+        "Whether each prover worker is currently computing a proof (1) or idle (0)",
+        &["setup", "vk_hash", "worker"]
+    )
+    .unwrap()
+});
+
+pub static PROVER_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "prover_queue_depth",
+        "Number of admitted requests that do not hold a prover worker yet"
+    )
+    .unwrap()
+});
+
+pub static PROVER_QUEUE_REJECTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_queue_rejections_total",
+        "Number of requests turned away by prover admission control, by reason",
+        &["reason"]
+    )
+    .unwrap()
+});
+
+pub static PROOF_CACHE_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "prover_proof_cache_hits_total",
+        "Number of prove requests answered from the proof cache"
+    )
+    .unwrap()
+});
+
+pub static PROOF_CACHE_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "prover_proof_cache_misses_total",
+        "Number of prove requests not found in the proof cache"
+    )
+    .unwrap()
+});
+
+pub static RATE_LIMITED_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_rate_limited_requests_total",
+        "Number of requests rejected by a rate limit, by the limit's scope",
+        &["scope"]
+    )
+    .unwrap()
+});
+
+pub static API_KEY_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_api_key_requests_total",
+        "Number of authenticated requests, by API key name",
+        &["api_key"]
+    )
+    .unwrap()
+});
+
+pub static SETUP_RELOADS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_setup_reloads_total",
+        "Number of circuit setup reloads, by setup and outcome",
+        &["setup", "outcome"]
+    )
+    .unwrap()
+});
+
+pub static CONFIG_RELOADS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_config_reloads_total",
+        "Number of config reloads, by outcome",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+pub static JWK_FETCHES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "prover_jwk_fetches_total",
+        "Number of JWK fetches from configured providers, by issuer and outcome",
+        &["issuer", "outcome"]
+    )
+    .unwrap()
+});
+
+pub static JWK_CACHE_AGE_SECS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "prover_jwk_cache_age_secs",
+        "Seconds since the cached JWKs of each issuer were last confirmed by its provider",
+        &["issuer"]
+    )
+    .unwrap()
+});
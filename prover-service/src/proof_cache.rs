@@ -0,0 +1,131 @@
+// Copyright © Aptos Foundation
+
+use crate::{api::ProverServiceResponse, metrics};
+use aptos_crypto::HashValue;
+use lru::LruCache;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// What a cached response is for: a request, proved with a setup, and checked and signed under
+/// the config and keys of a cache generation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProofCacheKey {
+    pub request_hash: HashValue,
+    /// The VK hash of the setup the request was admitted into.
+    pub vk_hash: HashValue,
+    /// See [`ProofCache::generation`].
+    pub generation: u64,
+}
+
+struct CachedProof {
+    response: ProverServiceResponse,
+    expires_at: Instant,
+}
+
+/// Bounded LRU cache of successful prove responses, keyed by the hash of the request, so that
+/// retries of an identical request do not redo witness generation and proving.
+///
+/// An entry never outlives the EPK expiry date of its request, since the proof is useless after.
+///
+/// [`ProofCache::clear`] starts a new generation: requests admitted before it are proved and
+/// checked as before, but their responses are not cached, since they may be for an old setup or
+/// may not pass the new config's checks.
+pub struct ProofCache {
+    entries: Mutex<LruCache<ProofCacheKey, CachedProof>>,
+    max_ttl: Duration,
+    generation: AtomicU64,
+}
+
+impl ProofCache {
+    pub fn new(capacity: usize, max_ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            max_ttl,
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of times the cache was cleared. Requests are keyed by the generation they were
+    /// admitted in.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// The key for a request admitted now, into the setup with this VK hash.
+    pub fn key(&self, request_hash: HashValue, vk_hash: HashValue) -> ProofCacheKey {
+        ProofCacheKey {
+            request_hash,
+            vk_hash,
+            generation: self.generation(),
+        }
+    }
+
+    /// The cached response for this key, if there is one and it has not expired.
+    pub fn get(&self, key: &ProofCacheKey) -> Option<ProverServiceResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let response = match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.response.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        };
+
+        match response {
+            Some(_) => metrics::PROOF_CACHE_HITS.inc(),
+            None => metrics::PROOF_CACHE_MISSES.inc(),
+        }
+        response
+    }
+
+    /// Cache a successful response. Errors are never cached, nor are responses for requests whose
+    /// EPK has already expired, or that were admitted before the cache was last cleared.
+    pub fn insert(&self, key: ProofCacheKey, exp_date_secs: u64, response: &ProverServiceResponse) {
+        if !matches!(response, ProverServiceResponse::Success { .. }) {
+            return;
+        }
+
+        let now_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let ttl = Duration::from_secs(exp_date_secs.saturating_sub(now_secs)).min(self.max_ttl);
+        if ttl.is_zero() {
+            return;
+        }
+
+        // Checked under the lock, so that `clear` cannot run in between.
+        let mut entries = self.entries.lock().unwrap();
+        if key.generation != self.generation() {
+            return;
+        }
+        entries.put(
+            key,
+            CachedProof {
+                response: response.clone(),
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every entry, and start a new generation.
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.clear();
+    }
+}
@@ -3,11 +3,14 @@
 // Import AsyncWriteExt for async writing
 
 use crate::config::ProverServiceConfig;
+use crate::training_wheels::signer::{InMemorySigner, TrainingWheelsSigner};
+use anyhow::{ensure, Result};
 use aptos_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey};
 use aptos_crypto::ValidCryptoMaterialStringExt;
 use serde::{Deserialize, Serialize};
 #[cfg(test)]
 use std::io::Write;
+use std::{collections::BTreeMap, sync::Arc};
 
 pub async fn cached_prover_key(config: &ProverServiceConfig) -> String {
     String::from(&config.resources_dir) + &config.zkey_filename
@@ -67,23 +70,63 @@ pub struct TrainingWheelsPubKey {
 
 #[derive(Debug)]
 pub struct TrainingWheelsKeyPair {
-    pub signing_key: Ed25519PrivateKey,
+    pub signer: Arc<dyn TrainingWheelsSigner>,
     pub verification_key: Ed25519PublicKey,
     pub on_chain_repr: OnChainKeylessConfiguration,
 }
 
 impl TrainingWheelsKeyPair {
     pub fn from_sk(sk: Ed25519PrivateKey) -> Self {
-        let verification_key = Ed25519PublicKey::from(&sk);
+        Self::from_signer(Arc::new(InMemorySigner::new(sk)))
+    }
+
+    pub fn from_signer(signer: Arc<dyn TrainingWheelsSigner>) -> Self {
+        let verification_key = signer.public_key().clone();
         let on_chain_repr = OnChainKeylessConfiguration::from_tw_pk(Some(verification_key.clone()));
         Self {
-            signing_key: sk,
+            signer,
             verification_key,
             on_chain_repr,
         }
     }
 }
 
+/// All training wheels keys, by key id, and which one to sign with. Keeping the others around
+/// lets the active key be switched back and forth during a rotation.
+#[derive(Debug)]
+pub struct TrainingWheelsKeys {
+    pub active_key_id: String,
+    pub keys: BTreeMap<String, TrainingWheelsKeyPair>,
+}
+
+impl TrainingWheelsKeys {
+    pub fn new(keys: BTreeMap<String, TrainingWheelsKeyPair>, active_key_id: &str) -> Result<Self> {
+        ensure!(
+            keys.contains_key(active_key_id),
+            "No training wheels key with id {active_key_id}; known ids: {:?}",
+            keys.keys().collect::<Vec<_>>()
+        );
+        Ok(Self {
+            active_key_id: String::from(active_key_id),
+            keys,
+        })
+    }
+
+    /// A single in-memory key, with id `0`.
+    pub fn from_sk(sk: Ed25519PrivateKey) -> Self {
+        Self::new(
+            BTreeMap::from([(String::from("0"), TrainingWheelsKeyPair::from_sk(sk))]),
+            "0",
+        )
+        .unwrap()
+    }
+
+    /// The key to sign with.
+    pub fn active(&self) -> &TrainingWheelsKeyPair {
+        &self.keys[&self.active_key_id]
+    }
+}
+
 /// This is not a UT, but a tool to convert a .vkey to its on-chain representation and save in a file.
 #[test]
 fn tw_vk_rewriter() {
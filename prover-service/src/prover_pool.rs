@@ -0,0 +1,304 @@
+// Copyright © Aptos Foundation
+
+use crate::metrics;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use rust_rapidsnark::{FullProver, ProverError};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Why a request was not given a prover worker.
+#[derive(Debug, thiserror::Error)]
+pub enum AdmissionError {
+    #[error("Prover queue is full ({max_depth} requests already waiting); try again later")]
+    QueueFull { max_depth: usize },
+    #[error("Timed out after {waited:?} waiting for a free prover; try again later")]
+    QueueTimeout { waited: Duration },
+}
+
+/// The setup a pool proves for, which tells its workers' metrics apart from those of other pools,
+/// e.g., of other setups, or of the version of the same setup that a reload replaced.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PoolLabels {
+    pub setup: String,
+    pub vk_hash: String,
+}
+
+/// How many pools are alive per `PoolLabels`. Pools with the same labels, e.g., after reloading
+/// an unchanged setup, share their workers' metrics, which are removed once the last of them is
+/// dropped.
+static LIVE_POOLS: Lazy<Mutex<HashMap<PoolLabels, usize>>> = Lazy::new(Default::default);
+
+/// Output of a single rapidsnark invocation, owned so that it can leave the blocking thread.
+pub struct ProverOutput {
+    pub proof_json: String,
+    pub prover_time_ms: f64,
+}
+
+/// A single rapidsnark instance. Each worker holds its own copy of the prover key.
+pub struct ProverWorker {
+    id: usize,
+    labels: [String; 3],
+    prover: Mutex<FullProver>,
+    /// When the worker was last checked out of the pool, if it is currently checked out.
+    busy_since: Mutex<Option<Instant>>,
+}
+
+impl ProverWorker {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    fn metric_labels(&self) -> [&str; 3] {
+        self.labels.each_ref().map(String::as_str)
+    }
+
+    fn set_busy(&self, busy: bool) {
+        *self.busy_since.lock().unwrap() = busy.then(Instant::now);
+        metrics::PROVER_WORKER_BUSY
+            .with_label_values(&self.metric_labels())
+            .set(busy as i64);
+    }
+}
+
+/// A fixed-size pool of `FullProver` workers.
+///
+/// Callers wait on a (FIFO-fair) semaphore for a free worker, and idle workers are handed out
+/// round-robin, so that load is spread evenly across them.
+///
+/// Requests are admitted into the pool's queue with [`ProverPool::enqueue`] when they arrive, and
+/// redeem their [`QueueTicket`] for a worker with [`ProverPool::acquire`] once their witness is
+/// ready. The queue is bounded both in depth and in how long a request may wait in it.
+pub struct ProverPool {
+    labels: PoolLabels,
+    workers: Vec<Arc<ProverWorker>>,
+    idle_workers: Arc<Mutex<VecDeque<usize>>>,
+    permits: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+    max_queue_depth: usize,
+    max_queue_wait: Option<Duration>,
+}
+
+impl ProverPool {
+    pub fn new(labels: PoolLabels, zkey_path: &str, num_workers: usize) -> Result<Self> {
+        let num_workers = num_workers.max(1);
+        let workers = (0..num_workers)
+            .map(|id| {
+                let prover = FullProver::new(zkey_path)
+                    .map_err(|e| anyhow!("failed to load prover worker {id}: {e}"))?;
+                Ok(Arc::new(ProverWorker {
+                    id,
+                    labels: [labels.setup.clone(), labels.vk_hash.clone(), id.to_string()],
+                    prover: Mutex::new(prover),
+                    busy_since: Mutex::new(None),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        *LIVE_POOLS
+            .lock()
+            .unwrap()
+            .entry(labels.clone())
+            .or_default() += 1;
+        for worker in &workers {
+            worker.set_busy(false);
+        }
+
+        Ok(Self {
+            labels,
+            workers,
+            idle_workers: Arc::new(Mutex::new((0..num_workers).collect())),
+            permits: Arc::new(Semaphore::new(num_workers)),
+            queued: Arc::new(AtomicUsize::new(0)),
+            max_queue_depth: usize::MAX,
+            max_queue_wait: None,
+        })
+    }
+
+    /// Bound the number of requests waiting for a worker, and how long each of them may wait.
+    pub fn with_queue_limits(mut self, max_depth: usize, max_wait: Duration) -> Self {
+        self.max_queue_depth = max_depth;
+        self.max_queue_wait = Some(max_wait);
+        self
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn num_idle_workers(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// For every worker that is currently checked out, how long it has been checked out for.
+    pub fn busy_workers(&self) -> Vec<(usize, Duration)> {
+        self.workers
+            .iter()
+            .filter_map(|worker| {
+                let busy_since = (*worker.busy_since.lock().unwrap())?;
+                Some((worker.id, busy_since.elapsed()))
+            })
+            .collect()
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Admit a newly-arrived request into the queue, or reject it if the queue is full.
+    pub fn enqueue(&self) -> Result<QueueTicket, AdmissionError> {
+        self.queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |depth| {
+                (depth < self.max_queue_depth).then_some(depth + 1)
+            })
+            .map_err(|_| {
+                metrics::PROVER_QUEUE_REJECTIONS
+                    .with_label_values(&["queue_full"])
+                    .inc();
+                AdmissionError::QueueFull {
+                    max_depth: self.max_queue_depth,
+                }
+            })?;
+        metrics::PROVER_QUEUE_DEPTH.inc();
+
+        Ok(QueueTicket {
+            arrived_at: Instant::now(),
+            queued: self.queued.clone(),
+        })
+    }
+
+    /// Wait until a worker is free and check it out of the pool. Fails if the request has already
+    /// spent more than the maximum queue wait time since it arrived.
+    pub async fn acquire(&self, ticket: QueueTicket) -> Result<ProverWorkerGuard, AdmissionError> {
+        let permit = self.permits.clone().acquire_owned();
+        let permit = match self.max_queue_wait {
+            Some(max_wait) => {
+                let deadline = tokio::time::Instant::from_std(ticket.arrived_at + max_wait);
+                tokio::time::timeout_at(deadline, permit)
+                    .await
+                    .map_err(|_| {
+                        metrics::PROVER_QUEUE_REJECTIONS
+                            .with_label_values(&["timeout"])
+                            .inc();
+                        AdmissionError::QueueTimeout {
+                            waited: ticket.arrived_at.elapsed(),
+                        }
+                    })?
+            }
+            None => permit.await,
+        }
+        .expect("prover pool semaphore is never closed");
+
+        metrics::REQUEST_QUEUE_TIME_SECS.observe(ticket.arrived_at.elapsed().as_secs_f64());
+        Ok(self.checkout(permit))
+    }
+
+    fn checkout(&self, permit: OwnedSemaphorePermit) -> ProverWorkerGuard {
+        let worker_id = self
+            .idle_workers
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("holding a permit guarantees an idle worker");
+        self.workers[worker_id].set_busy(true);
+        ProverWorkerGuard {
+            worker: self.workers[worker_id].clone(),
+            idle_workers: self.idle_workers.clone(),
+            _permit: permit,
+        }
+    }
+}
+
+impl Drop for ProverPool {
+    /// Once a pool is gone, e.g., a setup that was replaced by a reload and has finished its
+    /// in-flight requests, its workers' metrics would only ever report it as idle. Remove them.
+    fn drop(&mut self) {
+        let mut live_pools = LIVE_POOLS.lock().unwrap();
+        let Some(count) = live_pools.get_mut(&self.labels) else {
+            return;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        live_pools.remove(&self.labels);
+        for worker in &self.workers {
+            let _ = metrics::PROVER_WORKER_BUSY.remove_label_values(&worker.metric_labels());
+            let _ = metrics::PROVER_WORKER_PROOFS.remove_label_values(&worker.metric_labels());
+        }
+    }
+}
+
+/// A request's place in a [`ProverPool`]'s queue. Leaves the queue when dropped.
+pub struct QueueTicket {
+    arrived_at: Instant,
+    queued: Arc<AtomicUsize>,
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        metrics::PROVER_QUEUE_DEPTH.dec();
+    }
+}
+
+/// A worker checked out of a [`ProverPool`]. The worker goes back to the pool when this is dropped.
+pub struct ProverWorkerGuard {
+    worker: Arc<ProverWorker>,
+    idle_workers: Arc<Mutex<VecDeque<usize>>>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ProverWorkerGuard {
+    pub fn worker_id(&self) -> usize {
+        self.worker.id()
+    }
+
+    /// Run rapidsnark on a blocking thread, so that the async runtime stays responsive.
+    ///
+    /// The guard and the witness move into the blocking task, so the worker is only returned to
+    /// the pool, and a temporary witness file only deleted, once the FFI call has actually
+    /// finished, even if the request future is dropped in the meantime.
+    pub async fn prove<W>(self, witness: W) -> Result<ProverOutput, ProverError>
+    where
+        W: AsRef<Path> + Send + 'static,
+    {
+        tokio::task::spawn_blocking(move || {
+            let witness_path = witness.as_ref().to_str().ok_or(ProverError::InvalidInput)?;
+            let prover = self.worker.prover.lock().unwrap();
+            let (proof_json, internal_metrics) = prover.prove(witness_path)?;
+            metrics::PROVER_WORKER_PROOFS
+                .with_label_values(&self.worker.metric_labels())
+                .inc();
+            Ok(ProverOutput {
+                proof_json: proof_json.to_string(),
+                prover_time_ms: f64::from(internal_metrics.prover_time),
+            })
+        })
+        .await
+        .unwrap_or_else(|e| Err(ProverError::Unknown(join_error_message(&e))))
+    }
+}
+
+impl Drop for ProverWorkerGuard {
+    fn drop(&mut self) {
+        self.worker.set_busy(false);
+        self.idle_workers.lock().unwrap().push_back(self.worker.id);
+    }
+}
+
+fn join_error_message(e: &tokio::task::JoinError) -> &'static str {
+    if e.is_panic() {
+        "prover worker panicked"
+    } else {
+        "prover worker task was cancelled"
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    config::{IpRange, ProverServiceConfig, RateLimit},
+    error::{self, ErrorWithCode},
+    input_processing::types::VerifiedInput,
+    metrics,
+    state::ProverServiceState,
+};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
+
+/// How often buckets that have refilled completely are dropped, since they behave exactly like new
+/// ones.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// A token bucket per key (e.g., per client IP).
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: DashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Take a token from `key`'s bucket. If the bucket is empty, returns how long until it has a
+    /// token again.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let burst = f64::from(self.limit.burst);
+        let tokens_per_sec = self.limit.requests_per_minute / 60.0;
+        let now = Instant::now();
+
+        let mut bucket = self
+            .buckets
+            .entry(String::from(key))
+            .or_insert_with(|| TokenBucket {
+                tokens: burst,
+                last_refill: now,
+            });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * tokens_per_sec).min(burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if tokens_per_sec > 0.0 {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / tokens_per_sec,
+            ))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+
+    pub fn num_tracked_keys(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn evict_full_buckets(&self) {
+        let burst = f64::from(self.limit.burst);
+        let tokens_per_sec = self.limit.requests_per_minute / 60.0;
+        self.buckets.retain(|_, bucket| {
+            bucket.tokens + bucket.last_refill.elapsed().as_secs_f64() * tokens_per_sec < burst
+        });
+    }
+}
+
+/// The rate limiters configured in `ProverServiceConfig`.
+#[derive(Default)]
+pub struct RateLimiters {
+    per_client_ip: Option<RateLimiter>,
+    per_iss: Option<RateLimiter>,
+    per_uid: Option<RateLimiter>,
+}
+
+impl RateLimiters {
+    pub fn from_config(config: &ProverServiceConfig) -> Self {
+        Self {
+            per_client_ip: config.rate_limit_per_client_ip.map(RateLimiter::new),
+            per_iss: config.rate_limit_per_iss.map(RateLimiter::new),
+            per_uid: config.rate_limit_per_uid.map(RateLimiter::new),
+        }
+    }
+
+    pub fn evict_full_buckets(&self) {
+        for limiter in [&self.per_client_ip, &self.per_iss, &self.per_uid]
+            .into_iter()
+            .flatten()
+        {
+            limiter.evict_full_buckets();
+        }
+    }
+
+    pub fn check_client_ip(&self, ip: IpAddr) -> Result<(), ErrorWithCode> {
+        check(&self.per_client_ip, "client_ip", &ip.to_string())
+    }
+
+    /// Checks the `iss` and uid limits. Only called for requests that passed validation, so that
+    /// bogus JWTs cannot use up a real user's quota.
+    pub fn check_verified_input(&self, input: &VerifiedInput) -> Result<(), ErrorWithCode> {
+        let iss = &input.jwt.payload.iss;
+        check(&self.per_iss, "iss", iss)?;
+        check(
+            &self.per_uid,
+            "uid",
+            &format!("{iss}/{}/{}", input.uid_key, input.uid_val),
+        )
+    }
+}
+
+fn check(limiter: &Option<RateLimiter>, scope: &str, key: &str) -> Result<(), ErrorWithCode> {
+    let Some(limiter) = limiter else {
+        return Ok(());
+    };
+    limiter.check(key).map_err(|retry_after| {
+        metrics::RATE_LIMITED_REQUESTS
+            .with_label_values(&[scope])
+            .inc();
+        error::rate_limited(scope, retry_after)
+    })
+}
+
+/// The IP address of the client that sent a request with `headers` over a connection from `peer`.
+/// If `peer` is a trusted proxy, this is the rightmost address in `X-Forwarded-For` that is not,
+/// since only trusted proxies vouch for the address to their left.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpRange]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(ip));
+    let forwarded_for = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .collect::<Vec<_>>();
+
+    let mut client_ip = peer;
+    for forwarded_ip in forwarded_for.into_iter().rev() {
+        if !is_trusted(client_ip) {
+            break;
+        }
+        match forwarded_ip.trim().parse() {
+            Ok(forwarded_ip) => client_ip = forwarded_ip,
+            Err(_) => break,
+        }
+    }
+    client_ip
+}
+
+/// The client IP that `limit_by_client_ip` charged a request to, for handlers that charge it for
+/// more than one proof.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+/// Middleware that applies the per-client-IP rate limit, one token per request. Batch requests
+/// are charged for the rest of their proofs by the handler. Needs the router to be served with
+/// `into_make_service_with_connect_info::<SocketAddr>()`.
+pub async fn limit_by_client_ip(
+    State(state): State<Arc<ProverServiceState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(
+        addr.ip(),
+        request.headers(),
+        &state.config().trusted_proxies,
+    );
+    match state.rate_limiters.check_client_ip(ip) {
+        Ok(()) => {
+            request.extensions_mut().insert(ClientIp(ip));
+            next.run(request).await
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Periodically drop the buckets of keys that have not sent requests for long enough to be
+/// back at a full bucket, so that clients that come and go do not pile up.
+pub fn spawn_eviction_loop(state: Arc<ProverServiceState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            state.rate_limiters.evict_full_buckets();
+        }
+    })
+}
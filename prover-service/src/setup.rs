@@ -0,0 +1,408 @@
+// Copyright © Aptos Foundation
+
+use crate::{
+    api::SetupInfo,
+    config::ProverServiceConfig,
+    groth16_vk::OnChainGroth16VerificationKey,
+    handlers::encode_proof,
+    load_vk::try_prepared_vk,
+    metrics,
+    prover_pool::{PoolLabels, ProverPool},
+    state::ProverServiceState,
+    watcher::FileWatcher,
+    witness_gen::{witness_gen_from_file, witness_gen_paths},
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use aptos_crypto::HashValue;
+use aptos_keyless_common::input_processing::config::CircuitConfig;
+use ark_bn254::Bn254;
+use ark_ff::PrimeField;
+use ark_groth16::PreparedVerifyingKey;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::Duration,
+};
+use tempfile::TempPath;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Everything needed to prove for one circuit: the prover key, loaded into a pool of prover
+/// workers, and the matching VK and circuit config.
+pub struct CircuitSetup {
+    /// The setup's directory in `resources_dir`, which clients can select it by.
+    pub name: String,
+    /// The config this setup was loaded from. Its paths point to this setup's files, e.g., for
+    /// witness generation.
+    pub config: ProverServiceConfig,
+    pub circuit_config: CircuitConfig,
+    pub groth16_vk: OnChainGroth16VerificationKey,
+    /// See [`OnChainGroth16VerificationKey::hash`]. Clients can select the setup by this too.
+    pub vk_hash: HashValue,
+    pub prepared_vk: PreparedVerifyingKey<Bn254>,
+    pub prover_pool: ProverPool,
+}
+
+impl CircuitSetup {
+    /// Load the setup in `config.setup_dir`. Blocks for as long as it takes every prover worker
+    /// to load the prover key.
+    pub fn load(config: &ProverServiceConfig) -> Result<Self> {
+        let groth16_vk = config.load_vk()?;
+        let vk_hash = groth16_vk.hash()?;
+        let pool_labels = PoolLabels {
+            setup: config.setup_dir.clone(),
+            vk_hash: vk_hash.to_hex(),
+        };
+        Ok(Self {
+            name: config.setup_dir.clone(),
+            config: config.clone(),
+            circuit_config: config.load_circuit_params()?,
+            vk_hash,
+            groth16_vk,
+            prepared_vk: try_prepared_vk(&config.verification_key_path())?,
+            prover_pool: ProverPool::new(
+                pool_labels,
+                &config.zkey_path(),
+                config.num_prover_workers,
+            )?
+            .with_queue_limits(
+                config.max_prover_queue_depth,
+                Duration::from_secs(config.max_prover_queue_wait_secs),
+            ),
+        })
+    }
+
+    /// Load every setup in `config.setup_dirs()`, in that order.
+    pub fn load_all(config: &ProverServiceConfig) -> Result<Vec<Self>> {
+        config
+            .setup_dirs()
+            .into_iter()
+            .map(|setup_dir| {
+                Self::load(&config.for_setup_dir(setup_dir))
+                    .with_context(|| format!("failed to load setup {setup_dir}"))
+            })
+            .collect()
+    }
+
+    /// Prove the setup's self-test witness with the prover key, and verify the proof under the VK.
+    /// Fails if the two do not belong together, or if either is corrupt.
+    pub async fn self_test(&self) -> Result<()> {
+        ensure!(
+            self.prepared_vk.vk.gamma_abc_g1.len() == 2,
+            "expected a VK with a single public input, found {}",
+            self.prepared_vk.vk.gamma_abc_g1.len().saturating_sub(1)
+        );
+        let witness = self.self_test_witness().await?;
+        let witness_path = witness.as_ref().display().to_string();
+        let public_input = read_public_input(&witness_path)?;
+
+        let queue_ticket = self.prover_pool.enqueue()?;
+        let worker = self.prover_pool.acquire(queue_ticket).await?;
+        let output = worker
+            .prove(witness)
+            .await
+            .map_err(|e| anyhow!("failed to prove {witness_path}: {e}"))?;
+
+        let proof = encode_proof(&serde_json::from_str(&output.proof_json)?)?;
+        proof
+            .verify_proof(public_input, &self.prepared_vk)
+            .context("self-test proof does not verify under the VK")
+    }
+
+    /// The setup dir's self-test witness if it has one. Otherwise, one generated from the
+    /// configured self-test input with this setup's witness generator, which also checks that the
+    /// witness generator runs.
+    async fn self_test_witness(&self) -> Result<SelfTestWitness> {
+        let witness_path = self.config.setup_self_test_witness_path();
+        if Path::new(&witness_path).exists() {
+            return Ok(SelfTestWitness::Shipped(PathBuf::from(witness_path)));
+        }
+        let input_path = self.config.setup_self_test_input_path().ok_or_else(|| {
+            anyhow!(
+                "{witness_path} does not exist, and there is no setup_self_test_input_path to \
+                 generate it from"
+            )
+        })?;
+
+        let config = self.config.clone();
+        let witness_file =
+            tokio::task::spawn_blocking(move || witness_gen_from_file(&config, &input_path))
+                .await?
+                .context("failed to generate the self-test witness")?;
+        Ok(SelfTestWitness::Generated(witness_file.into_temp_path()))
+    }
+
+    /// Number of requests admitted into this setup's prover queue that have not finished yet.
+    pub fn num_in_flight_proofs(&self) -> usize {
+        self.prover_pool.queue_depth() + self.prover_pool.busy_workers().len()
+    }
+
+    /// Whether a request's `setup` field refers to this setup, by name or by (hex) VK hash.
+    pub fn is_named(&self, name_or_vk_hash: &str) -> bool {
+        name_or_vk_hash == self.name
+            || HashValue::from_hex(name_or_vk_hash.trim_start_matches("0x"))
+                .is_ok_and(|vk_hash| vk_hash == self.vk_hash)
+    }
+
+    pub fn info(&self, is_default: bool) -> SetupInfo {
+        SetupInfo {
+            name: self.name.clone(),
+            vk_hash: self.vk_hash,
+            is_default,
+            groth16_vk: self.groth16_vk.clone(),
+            max_lengths: self.circuit_config.max_lengths.clone(),
+        }
+    }
+
+    /// The files the setup in `config.setup_dir` is loaded from.
+    pub fn paths(config: &ProverServiceConfig) -> Vec<PathBuf> {
+        [
+            config.zkey_path(),
+            config.verification_key_path(),
+            config.circuit_config_path(),
+        ]
+        .into_iter()
+        .chain(witness_gen_paths(config))
+        .map(PathBuf::from)
+        .collect()
+    }
+}
+
+/// The witness that [`CircuitSetup::self_test`] proves. A generated one is deleted when dropped.
+enum SelfTestWitness {
+    Shipped(PathBuf),
+    Generated(TempPath),
+}
+
+impl AsRef<Path> for SelfTestWitness {
+    fn as_ref(&self) -> &Path {
+        match self {
+            SelfTestWitness::Shipped(path) => path,
+            SelfTestWitness::Generated(path) => path,
+        }
+    }
+}
+
+/// The setups that new requests are admitted into. The first one is the default, for requests
+/// that do not pick one.
+///
+/// Each setup can be swapped at runtime: requests keep the setup they were admitted into, so a
+/// replaced setup stays loaded until they have all finished.
+pub struct Setups {
+    current: RwLock<Vec<Arc<CircuitSetup>>>,
+    /// Setups that were swapped out, and may still be proving requests admitted before the swap.
+    retired: Mutex<Vec<Weak<CircuitSetup>>>,
+    reloading: tokio::sync::Mutex<()>,
+}
+
+impl Setups {
+    pub fn new(setups: Vec<CircuitSetup>) -> Self {
+        assert!(!setups.is_empty(), "at least one setup is needed");
+        Self {
+            current: RwLock::new(setups.into_iter().map(Arc::new).collect()),
+            retired: Mutex::new(vec![]),
+            reloading: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn default_setup(&self) -> Arc<CircuitSetup> {
+        self.current.read().unwrap()[0].clone()
+    }
+
+    /// The setup named by a request's `setup` field, or the default one if it has none.
+    pub fn select(&self, name_or_vk_hash: Option<&str>) -> Option<Arc<CircuitSetup>> {
+        match name_or_vk_hash {
+            None => Some(self.default_setup()),
+            Some(name_or_vk_hash) => self
+                .all()
+                .into_iter()
+                .find(|setup| setup.is_named(name_or_vk_hash)),
+        }
+    }
+
+    pub fn all(&self) -> Vec<Arc<CircuitSetup>> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn infos(&self) -> Vec<SetupInfo> {
+        self.all()
+            .iter()
+            .enumerate()
+            .map(|(i, setup)| setup.info(i == 0))
+            .collect()
+    }
+
+    /// Make `setup` the one new requests for its name are admitted into.
+    pub fn swap(&self, setup: CircuitSetup) -> Result<Arc<CircuitSetup>> {
+        let setup = Arc::new(setup);
+        let old = {
+            let mut current = self.current.write().unwrap();
+            let slot = current
+                .iter_mut()
+                .find(|current| current.name == setup.name)
+                .ok_or_else(|| anyhow!("no setup named {}", setup.name))?;
+            std::mem::replace(slot, setup.clone())
+        };
+
+        let mut retired = self.retired.lock().unwrap();
+        retired.retain(|setup| setup.strong_count() > 0);
+        retired.push(Arc::downgrade(&old));
+        Ok(setup)
+    }
+
+    /// The current setups, followed by every replaced setup that some request still holds on to.
+    pub fn live(&self) -> Vec<Arc<CircuitSetup>> {
+        let mut live = self.all();
+        live.extend(
+            self.retired
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(Weak::upgrade),
+        );
+        live
+    }
+}
+
+/// Load the setup named `name` from disk again and, if it passes its self-test, swap it in. Proof
+/// cache entries may have been proved with the old setup, so they are dropped.
+///
+/// Until the old setup's in-flight requests finish, both setups' prover keys are in memory. Only
+/// one reload runs at a time, so at most two copies of a setup are loaded at once.
+pub async fn reload(state: &ProverServiceState, name: &str) -> Result<Arc<CircuitSetup>> {
+    let _reloading = state.setups.reloading.lock().await;
+
+    let config = state.config().for_setup_dir(name);
+    let setup = tokio::task::spawn_blocking(move || CircuitSetup::load(&config))
+        .await?
+        .context("failed to load setup")?;
+    setup
+        .self_test()
+        .await
+        .context("setup failed its self-test")?;
+
+    let setup = state.setups.swap(setup)?;
+    state.proof_cache.clear();
+    Ok(setup)
+}
+
+/// [`reload`], logging and counting the outcome.
+pub async fn reload_and_log(state: &ProverServiceState, name: &str) -> Result<Arc<CircuitSetup>> {
+    info!(setup = name, "Reloading the circuit setup");
+    let result = reload(state, name).await;
+    match &result {
+        Ok(setup) => {
+            metrics::SETUP_RELOADS
+                .with_label_values(&[name, "success"])
+                .inc();
+            info!(
+                setup = name,
+                vk_hash = setup.vk_hash.to_hex(),
+                "Reloaded the circuit setup"
+            );
+        }
+        Err(e) => {
+            metrics::SETUP_RELOADS
+                .with_label_values(&[name, "failure"])
+                .inc();
+            error!(
+                setup = name,
+                error = format!("{e:#}"),
+                "Failed to reload the circuit setup; still using the old one"
+            );
+        }
+    }
+    result
+}
+
+/// For each setup, reload it whenever one of its files changes. Waits until the files have stopped
+/// changing for a whole poll interval first, so that a setup that is still being copied is not
+/// loaded.
+pub fn spawn_reload_loops(
+    state: Arc<ProverServiceState>,
+    poll_interval: Duration,
+) -> Vec<JoinHandle<()>> {
+    state
+        .setups
+        .all()
+        .iter()
+        .map(|setup| {
+            let state = state.clone();
+            let name = setup.name.clone();
+            let mut watcher = FileWatcher::new(CircuitSetup::paths(&setup.config));
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(poll_interval);
+                loop {
+                    interval.tick().await;
+                    if !watcher.poll_changed() {
+                        continue;
+                    }
+                    loop {
+                        interval.tick().await;
+                        if !watcher.poll_changed() {
+                            break;
+                        }
+                    }
+                    let _ = reload_and_log(&state, &name).await;
+                }
+            })
+        })
+        .collect()
+}
+
+/// Read the first public input out of a witness file in the `.wtns` format written by circom's
+/// witness generators. Public inputs directly follow the constant 1 at the start of the witness.
+pub fn read_public_input(witness_path: &str) -> Result<ark_bn254::Fr> {
+    let bytes = fs::read(witness_path).with_context(|| format!("failed to read {witness_path}"))?;
+    let mut reader = WtnsReader { bytes: &bytes };
+    ensure!(
+        reader.take(4)? == b"wtns",
+        "{witness_path} is not a .wtns file"
+    );
+    let _version = reader.u32()?;
+    let num_sections = reader.u32()?;
+
+    let mut field_size = None;
+    let mut values = None;
+    for _ in 0..num_sections {
+        let section_type = reader.u32()?;
+        let section_size = usize::try_from(reader.u64()?)?;
+        let mut section = WtnsReader {
+            bytes: reader.take(section_size)?,
+        };
+        match section_type {
+            1 => field_size = Some(section.u32()? as usize),
+            2 => values = Some(section.bytes),
+            _ => {}
+        }
+    }
+
+    let field_size = field_size.ok_or_else(|| anyhow!("{witness_path} has no header section"))?;
+    let values = values.ok_or_else(|| anyhow!("{witness_path} has no witness section"))?;
+    let public_input = values
+        .get(field_size..2 * field_size)
+        .ok_or_else(|| anyhow!("{witness_path} has no public input"))?;
+    Ok(ark_bn254::Fr::from_le_bytes_mod_order(public_input))
+}
+
+struct WtnsReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> WtnsReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.bytes.len() >= len, "truncated .wtns file");
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright © Aptos Foundation
+
+use crate::state::ProverServiceState;
+use std::time::Duration;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    time::Instant,
+};
+use tracing::{info, warn};
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Resolves once the process receives SIGTERM or SIGINT.
+pub async fn signal_received() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("Couldn't listen for SIGTERM");
+    let mut sigint = signal(SignalKind::interrupt()).expect("Couldn't listen for SIGINT");
+    tokio::select! {
+        _ = sigterm.recv() => info!("Received SIGTERM"),
+        _ = sigint.recv() => info!("Received SIGINT"),
+    }
+}
+
+/// Number of prove requests that were admitted and have not finished yet, whether they are still
+/// waiting for a prover worker or already hold one, across the current and any replaced setups.
+pub fn num_in_flight_proofs(state: &ProverServiceState) -> usize {
+    state
+        .setups
+        .live()
+        .iter()
+        .map(|setup| setup.num_in_flight_proofs())
+        .sum()
+}
+
+/// Stop admitting prove requests, and wait until those already admitted have finished, or until
+/// `timeout` has passed. Returns whether all of them finished.
+pub async fn drain(state: &ProverServiceState, timeout: Duration) -> bool {
+    state.begin_shutdown();
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let in_flight = num_in_flight_proofs(state);
+        if in_flight == 0 {
+            info!("All in-flight proofs finished");
+            return true;
+        }
+        if Instant::now() >= deadline {
+            warn!(
+                in_flight,
+                "Drain timeout passed with proofs still in flight; abandoning them"
+            );
+            return false;
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
@@ -1,47 +1,142 @@
+use anyhow::{ensure, Context, Result};
 use aptos_crypto::ed25519::Ed25519PrivateKey;
-use aptos_keyless_common::input_processing::config::CircuitConfig;
-use figment::{providers::Env, Figment};
-use rust_rapidsnark::FullProver;
+use figment::{
+    providers::{Env, Format, Yaml},
+    Figment,
+};
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
 
+use crate::auth::ApiKeyAuth;
 use crate::config::{ProverServiceConfig, CONFIG};
-use crate::groth16_vk::OnChainGroth16VerificationKey;
-use crate::prover_key::TrainingWheelsKeyPair;
-use tokio::sync::Mutex;
+use crate::jobs::JobStore;
+use crate::proof_cache::ProofCache;
+use crate::prover_key::{TrainingWheelsKeyPair, TrainingWheelsKeys};
+use crate::rate_limit::RateLimiters;
+use crate::setup::{CircuitSetup, Setups};
+use crate::training_wheels::signer;
 
+/// Training wheels private keys, by key id.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct ProverServiceSecrets {
-    /// The current training wheel key.
-    pub private_key_0: Ed25519PrivateKey,
+    pub private_keys: BTreeMap<String, Ed25519PrivateKey>,
+}
+
+impl ProverServiceSecrets {
+    /// Read the keys from the `PRIVATE_KEY_<id>` environment variables, and from
+    /// `config.tw_keys_file`, if set.
+    pub fn load(config: &ProverServiceConfig) -> Result<Self> {
+        let mut figment = Figment::new().merge(Env::prefixed("PRIVATE_KEY_"));
+        if let Some(tw_keys_file) = &config.tw_keys_file {
+            figment = figment.merge(Yaml::file_exact(tw_keys_file));
+        }
+        figment
+            .extract()
+            .context("Couldn't load training wheels keys")
+    }
+
+    /// Combine the keys with those of the signers in `config.tw_signers`.
+    pub fn into_tw_keys(self, config: &ProverServiceConfig) -> Result<TrainingWheelsKeys> {
+        let mut keys: BTreeMap<String, TrainingWheelsKeyPair> = self
+            .private_keys
+            .into_iter()
+            .map(|(key_id, sk)| (key_id, TrainingWheelsKeyPair::from_sk(sk)))
+            .collect();
+        for (key_id, signer_config) in &config.tw_signers {
+            let signer = signer::from_config(signer_config)
+                .with_context(|| format!("Couldn't load training wheels signer {key_id}"))?;
+            ensure!(
+                keys.insert(key_id.clone(), TrainingWheelsKeyPair::from_signer(signer))
+                    .is_none(),
+                "Training wheels key id {key_id} is used more than once"
+            );
+        }
+        TrainingWheelsKeys::new(keys, &config.active_tw_key_id)
+    }
+}
+
+/// A value that can be replaced at runtime. Readers get the value as it was when they asked, and
+/// can keep using it for as long as they hold on to it.
+pub struct Reloadable<T>(RwLock<Arc<T>>);
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(Arc::new(value)))
+    }
+
+    pub fn get(&self) -> Arc<T> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, value: T) {
+        *self.0.write().unwrap() = Arc::new(value);
+    }
 }
 
 pub struct ProverServiceState {
-    pub config: ProverServiceConfig,
-    pub circuit_metadata: CircuitConfig,
-    pub groth16_vk: OnChainGroth16VerificationKey,
-    pub tw_keys: TrainingWheelsKeyPair,
-    pub full_prover: Mutex<FullProver>,
+    /// Reloaded on SIGHUP or `POST /admin/reload_config`. See [`ProverServiceState::config`].
+    pub config: Reloadable<ProverServiceConfig>,
+    /// The circuit setups that new requests are proved with.
+    pub setups: Setups,
+    /// Reloaded along with `config`.
+    pub tw_keys: Reloadable<TrainingWheelsKeys>,
+    pub jobs: JobStore,
+    pub proof_cache: ProofCache,
+    pub rate_limiters: RateLimiters,
+    pub api_keys: ApiKeyAuth,
+    /// Set once the service received SIGTERM or SIGINT; from then on, no new prove requests are
+    /// admitted and readiness fails.
+    pub shutting_down: AtomicBool,
 }
 
 impl ProverServiceState {
     pub fn init() -> Self {
-        let ProverServiceSecrets {
-            private_key_0: private_key,
-        } = Figment::new()
-            .merge(Env::raw())
-            .extract()
-            .expect("Couldn't load private key from environment variable PRIVATE_KEY");
+        let tw_keys = ProverServiceSecrets::load(&CONFIG)
+            .and_then(|secrets| secrets.into_tw_keys(&CONFIG))
+            .expect(
+                "Couldn't load training wheels keys from environment variables PRIVATE_KEY_<id>",
+            );
 
         ProverServiceState {
-            config: CONFIG.clone(),
-            circuit_metadata: CONFIG.load_circuit_params(),
-            groth16_vk: CONFIG.load_vk(),
-            tw_keys: TrainingWheelsKeyPair::from_sk(private_key),
-            full_prover: Mutex::new(FullProver::new(&CONFIG.zkey_path()).unwrap()),
+            config: Reloadable::new(CONFIG.clone()),
+            setups: Setups::new(
+                CircuitSetup::load_all(&CONFIG).expect("Couldn't load the circuit setups"),
+            ),
+            tw_keys: Reloadable::new(tw_keys),
+            jobs: JobStore::new(
+                Duration::from_secs(CONFIG.job_ttl_secs),
+                Duration::from_secs(CONFIG.job_callback_timeout_secs),
+                CONFIG.job_callback_allowed_hosts.clone(),
+            ),
+            proof_cache: ProofCache::new(
+                CONFIG.proof_cache_capacity,
+                Duration::from_secs(CONFIG.proof_cache_max_ttl_secs),
+            ),
+            rate_limiters: RateLimiters::from_config(&CONFIG),
+            api_keys: ApiKeyAuth::from_config(&CONFIG.api_keys),
+            shutting_down: AtomicBool::new(false),
         }
     }
 
-    pub fn circuit_config(&self) -> &CircuitConfig {
-        &self.circuit_metadata
+    /// The current config. Settings that are only read at startup, like ports, TLS, the list of
+    /// setups, rate limits and API keys, keep their startup values until a restart.
+    pub fn config(&self) -> Arc<ProverServiceConfig> {
+        self.config.get()
+    }
+
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
     }
 }
@@ -0,0 +1,147 @@
+// Copyright © Aptos Foundation
+
+use crate::api::ErrorCode;
+use crate::auth::{
+    require_client_certificate, ApiKeyAuth, ClientCertificatePolicy, API_KEY_HEADER,
+};
+use crate::config::{ApiKey, RateLimit, TlsConfig};
+use crate::tests::common::get_config;
+use crate::tls::ClientCertificate;
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
+    middleware,
+    routing::get,
+    Router,
+};
+use tower::ServiceExt;
+
+fn api_keys() -> Vec<ApiKey> {
+    vec![
+        ApiKey {
+            name: String::from("partner-a"),
+            key: String::from("secret-a"),
+            quota: None,
+        },
+        ApiKey {
+            name: String::from("partner-b"),
+            key: String::from("secret-b"),
+            quota: Some(RateLimit {
+                requests_per_minute: 1.0,
+                burst: 1,
+            }),
+        },
+    ]
+}
+
+fn headers_with_key(key: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(API_KEY_HEADER, HeaderValue::from_static(key));
+    headers
+}
+
+#[test]
+fn no_configured_keys_means_auth_is_disabled() {
+    assert!(!ApiKeyAuth::from_config(&[]).is_enabled());
+    assert!(ApiKeyAuth::from_config(&api_keys()).is_enabled());
+}
+
+#[test]
+fn known_keys_are_authorized() {
+    let auth = ApiKeyAuth::from_config(&api_keys());
+    assert_eq!(
+        auth.authorize(&headers_with_key("secret-a")).unwrap(),
+        "partner-a"
+    );
+    assert_eq!(
+        auth.authorize(&headers_with_key("secret-b")).unwrap(),
+        "partner-b"
+    );
+}
+
+#[test]
+fn missing_or_unknown_keys_are_rejected() {
+    let auth = ApiKeyAuth::from_config(&api_keys());
+    for headers in [HeaderMap::new(), headers_with_key("secret-c")] {
+        let error = auth.authorize(&headers).unwrap_err();
+        assert_eq!(error.code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(error.error_code(), ErrorCode::Unauthorized);
+    }
+}
+
+#[test]
+fn keys_over_quota_are_rate_limited() {
+    let auth = ApiKeyAuth::from_config(&api_keys());
+    assert!(auth.authorize(&headers_with_key("secret-b")).is_ok());
+    let error = auth.authorize(&headers_with_key("secret-b")).unwrap_err();
+    assert_eq!(error.error_code(), ErrorCode::RateLimited);
+
+    // Other keys have their own quota.
+    assert!(auth.authorize(&headers_with_key("secret-a")).is_ok());
+}
+
+#[test]
+fn api_keys_are_not_leaked_by_config() {
+    let api_key = &api_keys()[0];
+    assert!(!format!("{api_key:?}").contains("secret-a"));
+    assert!(!serde_json::to_string(api_key).unwrap().contains("secret-a"));
+}
+
+fn tls_config(client_ca_path: Option<&str>) -> TlsConfig {
+    TlsConfig {
+        cert_path: String::from("./resources/test_tls/server.pem"),
+        key_path: String::from("./resources/test_tls/server.key"),
+        client_ca_path: client_ca_path.map(String::from),
+        reload_poll_secs: 10,
+    }
+}
+
+#[test]
+fn client_certificates_are_required_iff_a_client_ca_is_configured() {
+    let mut config = get_config();
+    config.tls = None;
+    assert!(!ClientCertificatePolicy::from_config(&config).required);
+    config.tls = Some(tls_config(None));
+    assert!(!ClientCertificatePolicy::from_config(&config).required);
+    config.tls = Some(tls_config(Some("./resources/test_tls/client_ca.pem")));
+    assert!(ClientCertificatePolicy::from_config(&config).required);
+}
+
+#[tokio::test]
+async fn client_certificates_are_only_required_where_configured() {
+    let app = Router::new()
+        .route("/v0/prove", get(|| async { "proved" }))
+        .route_layer(middleware::from_fn_with_state(
+            ClientCertificatePolicy { required: true },
+            require_client_certificate,
+        ))
+        .route("/healthcheck", get(|| async { "OK" }));
+
+    let request = |uri: &str, client_certificate: Option<ClientCertificate>| {
+        let mut request = Request::get(uri).body(Body::empty()).unwrap();
+        if let Some(client_certificate) = client_certificate {
+            request.extensions_mut().insert(client_certificate);
+        }
+        request
+    };
+    let status = |request: Request<Body>| {
+        let app = app.clone();
+        async move { app.oneshot(request).await.unwrap().status() }
+    };
+
+    let verified = Some(ClientCertificate { verified: true });
+    let anonymous = Some(ClientCertificate { verified: false });
+    assert_eq!(status(request("/v0/prove", verified)).await, StatusCode::OK);
+    assert_eq!(
+        status(request("/v0/prove", anonymous)).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        status(request("/v0/prove", None)).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        status(request("/healthcheck", anonymous)).await,
+        StatusCode::OK
+    );
+}
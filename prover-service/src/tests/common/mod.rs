@@ -9,7 +9,7 @@ use crate::{
     config::{self, ProverServiceConfig},
     handlers::prove_handler,
     jwk_fetching::{KeyID, DECODING_KEY_CACHE},
-    state::ProverServiceState,
+    state::{ProverServiceState, Reloadable},
 };
 use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
@@ -28,14 +28,22 @@ use figment::{
     Figment,
 };
 use rand::{rngs::ThreadRng, thread_rng};
-use rust_rapidsnark::FullProver;
 use serde::Serialize;
-use std::{marker::PhantomData, str::FromStr, sync::Arc};
-use tokio::sync::Mutex;
+use std::{
+    marker::PhantomData,
+    str::FromStr,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 
 pub mod types;
 
-use crate::prover_key::TrainingWheelsKeyPair;
+use crate::auth::ApiKeyAuth;
+use crate::jobs::JobStore;
+use crate::proof_cache::ProofCache;
+use crate::prover_key::TrainingWheelsKeys;
+use crate::rate_limit::RateLimiters;
+use crate::setup::{CircuitSetup, Setups};
 
 const TEST_JWK_EXPONENT_STR: &str = "65537";
 
@@ -90,9 +98,15 @@ pub fn get_config() -> ProverServiceConfig {
         .expect("Couldn't load config file")
 }
 
-pub async fn convert_prove_and_verify(
+/// Caches a fresh test JWK for `test.oidc.provider` and builds the service state for `testcase`.
+/// Returns the state, the JWK keypair to sign test JWTs with and the TW public key.
+pub fn init_test_state(
     testcase: &ProofTestCase<impl Serialize + WithNonce + Clone>,
-) -> Result<(), anyhow::Error> {
+) -> (
+    Arc<ProverServiceState>,
+    impl TestJWKKeyPair,
+    Ed25519PublicKey,
+) {
     let jwk_keypair = gen_test_jwk_keypair();
     let (tw_sk_default, tw_pk) = gen_test_training_wheels_keypair();
 
@@ -111,15 +125,34 @@ pub async fn convert_prove_and_verify(
     );
 
     let state = ProverServiceState {
-        config: testcase.prover_service_config.clone(),
-        circuit_metadata: testcase.prover_service_config.load_circuit_params(),
-        groth16_vk: testcase.prover_service_config.load_vk(),
-        tw_keys: TrainingWheelsKeyPair::from_sk(tw_sk_default),
-        full_prover: Mutex::new(
-            FullProver::new(testcase.prover_service_config.zkey_path().as_str()).unwrap(),
+        config: Reloadable::new(testcase.prover_service_config.clone()),
+        setups: Setups::new(CircuitSetup::load_all(&testcase.prover_service_config).unwrap()),
+        tw_keys: Reloadable::new(TrainingWheelsKeys::from_sk(tw_sk_default)),
+        jobs: JobStore::new(
+            Duration::from_secs(testcase.prover_service_config.job_ttl_secs),
+            Duration::from_secs(testcase.prover_service_config.job_callback_timeout_secs),
+            testcase
+                .prover_service_config
+                .job_callback_allowed_hosts
+                .clone(),
         ),
+        proof_cache: ProofCache::new(
+            testcase.prover_service_config.proof_cache_capacity,
+            Duration::from_secs(testcase.prover_service_config.proof_cache_max_ttl_secs),
+        ),
+        rate_limiters: RateLimiters::from_config(&testcase.prover_service_config),
+        api_keys: ApiKeyAuth::from_config(&testcase.prover_service_config.api_keys),
+        shutting_down: AtomicBool::new(false),
     };
 
+    (Arc::new(state), jwk_keypair, tw_pk)
+}
+
+pub async fn convert_prove_and_verify(
+    testcase: &ProofTestCase<impl Serialize + WithNonce + Clone>,
+) -> Result<(), anyhow::Error> {
+    let (state, jwk_keypair, tw_pk) = init_test_state(testcase);
+
     let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
 
     println!(
@@ -128,7 +161,7 @@ pub async fn convert_prove_and_verify(
     );
 
     let r = prove_handler(
-        State(Arc::new(state)),
+        State(state.clone()),
         WithRejection(Json(prover_request_input), PhantomData),
     )
     .await;
@@ -145,9 +178,14 @@ pub async fn convert_prove_and_verify(
         } => {
             let g16vk = prepared_vk(&testcase.prover_service_config.verification_key_path());
             proof.verify_proof(public_inputs_hash.as_fr(), &g16vk)?;
-            training_wheels::verify(&response, &tw_pk)
+            training_wheels::verify(
+                &response,
+                &tw_pk,
+                testcase.prover_service_config.training_wheels_signing_mode,
+                &state.setups.default_setup().vk_hash,
+            )
         }
-        ProverServiceResponse::Error { message } => {
+        ProverServiceResponse::Error { message, .. } => {
             panic!("returned ProverServiceResponse::Error: {}", message)
         }
     }
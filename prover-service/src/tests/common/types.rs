@@ -57,6 +57,15 @@ impl WithNonce for TestJWTPayload {
     }
 }
 
+/// For payloads that [`TestJWTPayload`] can't represent.
+impl WithNonce for serde_json::Value {
+    fn with_nonce(&self, nonce: &str) -> Self {
+        let mut payload = self.clone();
+        payload["nonce"] = serde_json::Value::from(nonce);
+        payload
+    }
+}
+
 impl Default for TestJWTPayload {
     fn default() -> Self {
         let start = SystemTime::now();
@@ -91,6 +100,7 @@ pub trait TestJWKKeyPair {
     fn pubkey_mod_b64(&self) -> String;
     fn kid(&self) -> &str;
     fn sign(&self, payload: &impl Serialize) -> String;
+    fn sign_with_alg(&self, payload: &impl Serialize, alg: Algorithm) -> String;
     #[allow(clippy::all)]
     fn into_rsa_jwk(&self) -> RSA_JWK;
 }
@@ -125,14 +135,8 @@ impl TestJWKKeyPair for DefaultTestJWKKeyPair {
         &self.kid
     }
 
-    #[allow(clippy::all)]
     fn sign(&self, payload: &impl Serialize) -> String {
-        let mut header = Header::default();
-        header.alg = Algorithm::RS256;
-        header.kid = Some(self.kid.clone());
-
-        let jwt =
-            jsonwebtoken::encode(&header, &payload, &self.private_key.as_encoding_key()).unwrap();
+        let jwt = self.sign_with_alg(payload, Algorithm::RS256);
 
         let jwk = RSA_JWK::new_256_aqab(self.kid.as_str(), &self.pubkey_mod_b64());
         assert!(jwk.verify_signature_without_exp_check(&jwt).is_ok());
@@ -140,6 +144,15 @@ impl TestJWKKeyPair for DefaultTestJWKKeyPair {
         jwt
     }
 
+    #[allow(clippy::all)]
+    fn sign_with_alg(&self, payload: &impl Serialize, alg: Algorithm) -> String {
+        let mut header = Header::default();
+        header.alg = alg;
+        header.kid = Some(self.kid.clone());
+
+        jsonwebtoken::encode(&header, &payload, &self.private_key.as_encoding_key()).unwrap()
+    }
+
     fn into_rsa_jwk(&self) -> RSA_JWK {
         RSA_JWK::new_256_aqab(&self.kid, &self.pubkey_mod_b64())
     }
@@ -174,7 +187,7 @@ impl<T: Serialize + WithNonce + Clone> ProofTestCase<T> {
     ) -> Self {
         assert!(*LOCAL_SETUP_PROCURED);
         let prover_service_config = get_config();
-        let circuit_metadata = prover_service_config.load_circuit_params();
+        let circuit_metadata = prover_service_config.load_circuit_params().unwrap();
         let epk = gen_test_ephemeral_pk();
         let epk_blinder = gen_test_ephemeral_pk_blinder();
         let nonce = compute_nonce(exp_date, &epk, epk_blinder, &circuit_metadata).unwrap();
@@ -218,7 +231,7 @@ impl<T: Serialize + WithNonce + Clone> ProofTestCase<T> {
 
     pub fn compute_nonce(self) -> Self {
         assert!(*LOCAL_SETUP_PROCURED);
-        let circuit_metadata = self.prover_service_config.load_circuit_params();
+        let circuit_metadata = self.prover_service_config.load_circuit_params().unwrap();
         let nonce = compute_nonce(
             self.epk_expiry_time_secs,
             &self.epk,
@@ -249,6 +262,7 @@ impl<T: Serialize + WithNonce + Clone> ProofTestCase<T> {
             idc_aud: self.idc_aud.clone(),
             use_insecure_test_jwk: false,
             skip_aud_checks: self.skip_aud_checks,
+            setup: None,
         }
     }
 }
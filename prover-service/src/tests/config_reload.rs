@@ -0,0 +1,70 @@
+// Copyright © Aptos Foundation
+
+use crate::config::ProverServiceConfig;
+use crate::config_reload::apply_config;
+use crate::prover_key::{TrainingWheelsKeyPair, TrainingWheelsKeys};
+use crate::tests::common::{
+    gen_test_training_wheels_keypair, init_test_state,
+    types::{ProofTestCase, TestJWTPayload},
+};
+use aptos_crypto::ValidCryptoMaterialStringExt;
+use serial_test::serial;
+use std::{collections::BTreeMap, io::Write};
+
+#[test]
+fn tw_keys_need_the_active_key() {
+    let keys = || {
+        BTreeMap::from([(
+            String::from("a"),
+            TrainingWheelsKeyPair::from_sk(gen_test_training_wheels_keypair().0),
+        )])
+    };
+    assert!(TrainingWheelsKeys::new(keys(), "b").is_err());
+    assert_eq!(
+        TrainingWheelsKeys::new(keys(), "a").unwrap().active_key_id,
+        "a"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn reload_switches_the_active_tw_key() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let (state, _, _) = init_test_state(&testcase);
+
+    let (old_sk, old_pk) = gen_test_training_wheels_keypair();
+    let (new_sk, new_pk) = gen_test_training_wheels_keypair();
+    let mut tw_keys_file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(
+        tw_keys_file,
+        "old: \"{}\"\nnew: \"{}\"",
+        old_sk.to_encoded_string().unwrap(),
+        new_sk.to_encoded_string().unwrap()
+    )
+    .unwrap();
+
+    let config_with_active_key = |active_tw_key_id: &str| ProverServiceConfig {
+        active_tw_key_id: String::from(active_tw_key_id),
+        tw_keys_file: Some(tw_keys_file.path().to_str().unwrap().to_owned()),
+        oidc_providers: vec![],
+        ..testcase.prover_service_config.clone()
+    };
+
+    apply_config(&state, config_with_active_key("old"))
+        .await
+        .unwrap();
+    assert_eq!(state.tw_keys.get().active().verification_key, old_pk);
+
+    apply_config(&state, config_with_active_key("new"))
+        .await
+        .unwrap();
+    assert_eq!(state.tw_keys.get().active().verification_key, new_pk);
+    assert_eq!(state.config().active_tw_key_id, "new");
+
+    // An unknown key id must not take effect.
+    assert!(apply_config(&state, config_with_active_key("missing"))
+        .await
+        .is_err());
+    assert_eq!(state.tw_keys.get().active().verification_key, new_pk);
+    assert_eq!(state.config().active_tw_key_id, "new");
+}
@@ -0,0 +1,80 @@
+// Copyright © Aptos Foundation
+
+use crate::api::{ErrorCode, ProverServiceResponse};
+use crate::error::{self, CodedError, ErrorWithCode, WithErrorCode};
+use anyhow::anyhow;
+use axum::http::StatusCode;
+
+#[test]
+fn error_codes_serialize_in_screaming_snake_case() {
+    assert_eq!(
+        serde_json::to_string(&ErrorCode::NonceMismatch).unwrap(),
+        "\"NONCE_MISMATCH\""
+    );
+    assert_eq!(
+        serde_json::to_string(&ErrorCode::JwkNotFound).unwrap(),
+        "\"JWK_NOT_FOUND\""
+    );
+}
+
+#[test]
+fn error_response_carries_error_code() {
+    let response = ProverServiceResponse::Error {
+        error_code: ErrorCode::EpkExpired,
+        message: String::from("expired"),
+    };
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["error_code"], "EPK_EXPIRED");
+    assert_eq!(json["message"], "expired");
+
+    // Responses from older servers, without an error code, still parse.
+    let response: ProverServiceResponse =
+        serde_json::from_str(r#"{"message": "something went wrong"}"#).unwrap();
+    assert!(matches!(
+        response,
+        ProverServiceResponse::Error {
+            error_code: ErrorCode::InternalError,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn error_code_survives_context_and_keeps_message() {
+    let result: anyhow::Result<()> = Err(anyhow!("unknown kid: abc"));
+    let error: ErrorWithCode = result
+        .with_error_code(ErrorCode::JwkNotFound)
+        .unwrap_err()
+        .into();
+    let error = error.context("while fetching JWK");
+
+    assert_eq!(error.error_code(), ErrorCode::JwkNotFound);
+    assert_eq!(error.code(), StatusCode::BAD_REQUEST);
+
+    let error: ErrorWithCode =
+        anyhow::Error::from(CodedError::msg(ErrorCode::NonceMismatch, "nonce mismatch")).into();
+    assert_eq!(error.error.to_string(), "nonce mismatch");
+}
+
+#[test]
+fn untagged_errors_get_generic_error_code() {
+    assert_eq!(
+        error::bad_request(anyhow!("bad")).error_code(),
+        ErrorCode::InvalidRequest
+    );
+    assert_eq!(
+        error::server_error(anyhow!("oops")).error_code(),
+        ErrorCode::InternalError
+    );
+    assert_eq!(
+        ErrorWithCode::from(anyhow!("oops")).code(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
+
+#[test]
+fn explicit_status_wins_over_error_code() {
+    let error = error::server_error(CodedError::msg(ErrorCode::NonceMismatch, "mismatch").into());
+    assert_eq!(error.code(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(error.error_code(), ErrorCode::NonceMismatch);
+}
@@ -0,0 +1,87 @@
+// Copyright © Aptos Foundation
+
+use crate::config::OidcProvider;
+use crate::health::{
+    check_jwks_cached, check_no_wedged_workers, check_setup_files_present, HealthReport,
+};
+use crate::jwk_fetching::DECODING_KEY_CACHE;
+use crate::tests::common::{gen_test_jwk_keypair, types::TestJWKKeyPair};
+use dashmap::DashMap;
+use std::{sync::Arc, time::Duration};
+
+fn provider(iss: &str) -> OidcProvider {
+    OidcProvider {
+        iss: String::from(iss),
+        endpoint_url: Some(String::from("https://example.com/jwks")),
+        fetch_timeout_secs: 5,
+        uid_keys: vec![],
+        allowed_auds: vec![],
+        max_exp_horizon_secs: None,
+        iat_leeway_secs: 0,
+        exp_leeway_secs: 0,
+        require_email_verified: false,
+        allow_skip_aud_checks: true,
+    }
+}
+
+#[test]
+fn jwks_check_fails_for_issuer_without_cached_keys() {
+    let jwk = gen_test_jwk_keypair().into_rsa_jwk();
+    DECODING_KEY_CACHE.insert(
+        String::from("health.test.cached"),
+        DashMap::from_iter([(jwk.kid.clone(), Arc::new(jwk))]),
+    );
+    DECODING_KEY_CACHE.insert(String::from("health.test.empty"), DashMap::new());
+
+    assert!(check_jwks_cached(&[provider("health.test.cached")]).ok);
+
+    let check = check_jwks_cached(&[
+        provider("health.test.cached"),
+        provider("health.test.empty"),
+        provider("health.test.missing"),
+    ]);
+    assert!(!check.ok);
+    assert!(!check.detail.contains("health.test.cached"));
+    assert!(check.detail.contains("health.test.empty"));
+    assert!(check.detail.contains("health.test.missing"));
+}
+
+#[test]
+fn setup_files_check_fails_for_missing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let present = dir.path().join("prover_key.zkey");
+    let missing = dir.path().join("verification_key.json");
+    std::fs::write(&present, "zkey").unwrap();
+
+    assert!(check_setup_files_present(&[present.clone()]).ok);
+
+    let check = check_setup_files_present(&[present, missing]);
+    assert!(!check.ok);
+    assert!(!check.detail.contains("prover_key.zkey"));
+    assert!(check.detail.contains("verification_key.json"));
+}
+
+#[test]
+fn wedged_worker_check_respects_deadline() {
+    let deadline = Duration::from_secs(60);
+    assert!(check_no_wedged_workers(&[], deadline).ok);
+    assert!(check_no_wedged_workers(&[(0, Duration::from_secs(59))], deadline).ok);
+
+    let check = check_no_wedged_workers(
+        &[(0, Duration::from_secs(1)), (1, Duration::from_secs(61))],
+        deadline,
+    );
+    assert!(!check.ok);
+    assert!(check.detail.contains("worker 1"));
+}
+
+#[test]
+fn report_fails_if_any_check_fails() {
+    let deadline = Duration::from_secs(60);
+    let report = HealthReport::from_checks(vec![
+        check_no_wedged_workers(&[], deadline),
+        check_no_wedged_workers(&[(0, Duration::from_secs(61))], deadline),
+    ]);
+    assert!(!report.ok);
+    assert_eq!(report.status_code(), http::StatusCode::SERVICE_UNAVAILABLE);
+}
@@ -0,0 +1,239 @@
+// Copyright © Aptos Foundation
+
+use crate::api::{CreateJobRequest, ErrorCode, JobResponse, JobStatus, ProverServiceResponse};
+use crate::handlers::{create_job_handler, get_job_handler};
+use crate::jobs::{is_public_ip, JobStore};
+use crate::tests::common::{
+    init_test_state,
+    types::{ProofTestCase, TestJWTPayload},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+    Json, Router,
+};
+use axum_extra::extract::WithRejection;
+use serial_test::serial;
+use std::{marker::PhantomData, time::Duration};
+use tokio::sync::mpsc;
+
+fn failed_response() -> ProverServiceResponse {
+    ProverServiceResponse::Error {
+        error_code: ErrorCode::NonceMismatch,
+        message: String::from("nonce mismatch"),
+    }
+}
+
+#[test]
+fn job_goes_from_pending_to_finished() {
+    let jobs = JobStore::new(Duration::from_secs(60), Duration::from_secs(1), vec![]);
+    let job_id = jobs.create(None).unwrap();
+    assert_eq!(jobs.get(&job_id).unwrap().status, JobStatus::Pending);
+
+    jobs.set_running(&job_id);
+    assert_eq!(jobs.get(&job_id).unwrap().status, JobStatus::Running);
+
+    futures::executor::block_on(jobs.finish(&job_id, failed_response()));
+    let job = jobs.get(&job_id).unwrap();
+    assert_eq!(job.status, JobStatus::Failed);
+    assert!(matches!(
+        job.response,
+        Some(ProverServiceResponse::Error {
+            error_code: ErrorCode::NonceMismatch,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn finished_jobs_expire_but_unfinished_ones_do_not() {
+    let jobs = JobStore::new(Duration::ZERO, Duration::from_secs(1), vec![]);
+    let finished_job_id = jobs.create(None).unwrap();
+    let running_job_id = jobs.create(None).unwrap();
+    jobs.set_running(&running_job_id);
+
+    futures::executor::block_on(jobs.finish(&finished_job_id, failed_response()));
+    std::thread::sleep(Duration::from_millis(10));
+
+    assert!(jobs.get(&finished_job_id).is_none());
+    assert!(jobs.get(&running_job_id).is_some());
+    jobs.evict_expired();
+    assert_eq!(jobs.len(), 1);
+}
+
+#[test]
+fn expired_jobs_are_evicted_when_looked_up() {
+    let jobs = JobStore::new(Duration::ZERO, Duration::from_secs(1), vec![]);
+    let job_id = jobs.create(None).unwrap();
+    futures::executor::block_on(jobs.finish(&job_id, failed_response()));
+    std::thread::sleep(Duration::from_millis(10));
+    assert_eq!(jobs.len(), 1);
+
+    assert!(jobs.get(&job_id).is_none());
+    assert!(jobs.is_empty());
+}
+
+#[test]
+fn non_http_callback_urls_are_rejected() {
+    let jobs = JobStore::new(Duration::from_secs(60), Duration::from_secs(1), vec![]);
+    assert!(jobs
+        .create(Some(String::from("file:///etc/passwd")))
+        .is_err());
+    assert!(jobs.create(Some(String::from("not a url"))).is_err());
+    assert!(jobs.is_empty());
+}
+
+#[test]
+fn callback_urls_to_internal_addresses_are_rejected() {
+    let jobs = JobStore::new(Duration::from_secs(60), Duration::from_secs(1), vec![]);
+    for callback_url in [
+        "http://127.0.0.1:8080/callback",
+        "http://10.0.0.1/callback",
+        "http://169.254.169.254/latest/meta-data",
+        "http://[::1]/callback",
+        "http://[fd00:ec2::254]/callback",
+        "http://[::ffff:192.168.0.1]/callback",
+    ] {
+        assert!(jobs.create(Some(String::from(callback_url))).is_err());
+    }
+    assert!(jobs.is_empty());
+
+    // Host names are checked when they are resolved.
+    assert!(jobs
+        .create(Some(String::from("https://example.com/callback")))
+        .is_ok());
+    assert!(jobs
+        .create(Some(String::from("https://93.184.215.14/callback")))
+        .is_ok());
+}
+
+#[test]
+fn callback_urls_must_be_on_allowed_hosts_if_any() {
+    let jobs = JobStore::new(
+        Duration::from_secs(60),
+        Duration::from_secs(1),
+        vec![String::from("callbacks.internal")],
+    );
+    assert!(jobs
+        .create(Some(String::from("http://callbacks.internal/done")))
+        .is_ok());
+    assert!(jobs
+        .create(Some(String::from("https://example.com/callback")))
+        .is_err());
+}
+
+#[test]
+fn public_ips_are_told_apart_from_internal_ones() {
+    for ip in ["93.184.215.14", "2606:2800:21f:cb07:6820:80da:af6b:8b2c"] {
+        assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+    }
+    for ip in [
+        "0.0.0.0",
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "255.255.255.255",
+        "::",
+        "::1",
+        "fe80::1",
+        "fc00::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+    }
+}
+
+#[tokio::test]
+async fn callbacks_to_host_names_with_internal_addresses_are_not_sent() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/callback",
+        post(move || async move {
+            sender.send(()).unwrap();
+            StatusCode::OK
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let jobs = JobStore::new(Duration::from_secs(60), Duration::from_secs(5), vec![]);
+    let job_id = jobs
+        .create(Some(format!("http://localhost:{port}/callback")))
+        .unwrap();
+    // `finish` returns once the callback was sent, or failed to be.
+    jobs.finish(&job_id, failed_response()).await;
+    assert!(receiver.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn callback_url_is_notified_when_job_finishes() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/callback",
+        post(move |Json(job): Json<JobResponse>| async move {
+            sender.send(job).unwrap();
+            StatusCode::OK
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let jobs = JobStore::new(
+        Duration::from_secs(60),
+        Duration::from_secs(5),
+        vec![String::from("127.0.0.1")],
+    );
+    let job_id = jobs
+        .create(Some(format!("http://{addr}/callback")))
+        .unwrap();
+    jobs.finish(&job_id, failed_response()).await;
+
+    let notification = receiver.recv().await.unwrap();
+    assert_eq!(notification.job_id, job_id);
+    assert_eq!(notification.status, JobStatus::Failed);
+}
+
+#[tokio::test]
+#[serial]
+async fn job_api_proves_in_the_background() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let body = CreateJobRequest {
+        request: testcase.convert_to_prover_request(&jwk_keypair),
+        callback_url: None,
+    };
+
+    let (status, Json(job)) =
+        create_job_handler(State(state.clone()), WithRejection(Json(body), PhantomData))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(job.status, JobStatus::Pending);
+
+    let job = loop {
+        let Json(job) = get_job_handler(State(state.clone()), Path(job.job_id.clone()))
+            .await
+            .unwrap();
+        if matches!(job.status, JobStatus::Done | JobStatus::Failed) {
+            break job;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    assert_eq!(job.status, JobStatus::Done);
+    assert!(matches!(
+        job.response,
+        Some(ProverServiceResponse::Success { .. })
+    ));
+
+    let error = get_job_handler(State(state), Path(String::from("no-such-job")))
+        .await
+        .unwrap_err();
+    assert_eq!(error.error_code(), ErrorCode::JobNotFound);
+    assert_eq!(error.code(), StatusCode::NOT_FOUND);
+}
@@ -1,7 +1,30 @@
-use crate::jwk_fetching::get_federated_jwk;
+use crate::config::{self, IssuerPattern, OidcProvider, ProverServiceConfig};
+use crate::jwk_fetching::{
+    cache_control_max_age, cached_decoding_key, check_federated_jwks_uri, discover_jwks_uri,
+    get_federated_jwk, is_federated_issuer, parse_jwk, update_jwk_fetching, JwkFetchSettings,
+};
 use crate::tests::common::gen_test_jwk_keypair_with_kid_override;
 use crate::tests::common::types::{ProofTestCase, TestJWTPayload};
 use aptos_keyless_common::input_processing::encoding::DecodedJWT;
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use figment::{
+    providers::{Format, Yaml},
+    Figment,
+};
+use serde_json::{json, Value};
+use serial_test::serial;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 // This test uses a demo auth0 tenant owned by oliver.he@aptoslabs.com
 #[tokio::test]
@@ -19,7 +42,12 @@ async fn test_federated_jwk_fetch() {
     let jwk_keypair = gen_test_jwk_keypair_with_kid_override(kid);
     let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
     let jwt = DecodedJWT::from_b64(&prover_request_input.jwt_b64).unwrap();
-    assert!(get_federated_jwk(&jwt).await.is_ok());
+    assert!(get_federated_jwk(
+        &testcase.prover_service_config.federated_issuer_patterns,
+        &jwt
+    )
+    .await
+    .is_ok());
 }
 
 #[tokio::test]
@@ -37,7 +65,13 @@ async fn test_federated_jwk_fetch_fails_for_bad_iss() {
     let jwk_keypair = gen_test_jwk_keypair_with_kid_override(kid);
     let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
     let jwt = DecodedJWT::from_b64(&prover_request_input.jwt_b64).unwrap();
-    let error_message = get_federated_jwk(&jwt).await.unwrap_err().to_string();
+    let error_message = get_federated_jwk(
+        &testcase.prover_service_config.federated_issuer_patterns,
+        &jwt,
+    )
+    .await
+    .unwrap_err()
+    .to_string();
 
     assert!(error_message.contains("not a federated iss"))
 }
@@ -57,7 +91,376 @@ async fn test_federated_jwk_fetch_fails_for_bad_kid() {
     let jwk_keypair = gen_test_jwk_keypair_with_kid_override(kid);
     let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
     let jwt = DecodedJWT::from_b64(&prover_request_input.jwt_b64).unwrap();
-    let error_message = get_federated_jwk(&jwt).await.unwrap_err().to_string();
+    let error_message = get_federated_jwk(
+        &testcase.prover_service_config.federated_issuer_patterns,
+        &jwt,
+    )
+    .await
+    .unwrap_err()
+    .to_string();
 
     assert!(error_message.contains("unknown kid"))
 }
+
+#[test]
+fn test_parse_jwk_keeps_alg_and_any_exponent() {
+    let jwk =
+        parse_jwk(&json!({"kty": "RSA", "kid": "k1", "alg": "RS384", "e": "Aw", "n": "AQAB"}))
+            .unwrap();
+    assert_eq!(jwk.alg, "RS384");
+    assert_eq!(jwk.e, "Aw");
+
+    let jwk = parse_jwk(&json!({"kty": "RSA", "kid": "k2", "e": "AQAB", "n": "AQAB"})).unwrap();
+    assert_eq!(jwk.alg, "");
+
+    assert!(parse_jwk(&json!("not a jwk")).is_err());
+}
+
+#[test]
+fn test_cache_control_max_age() {
+    assert_eq!(
+        cache_control_max_age("public, max-age=21600, must-revalidate"),
+        Some(Duration::from_secs(21600))
+    );
+    assert_eq!(cache_control_max_age("public"), None);
+    assert_eq!(cache_control_max_age("no-cache, max-age=60"), None);
+    assert_eq!(cache_control_max_age("max-age=soon"), None);
+}
+
+fn test_fetch_settings(grace_period: Duration) -> JwkFetchSettings {
+    JwkFetchSettings {
+        refresh_interval: Duration::from_millis(50),
+        max_refresh_interval: Duration::from_millis(50),
+        max_backoff: Duration::from_millis(200),
+        grace_period,
+    }
+}
+
+#[test]
+fn test_jwk_fetch_delays() {
+    let settings = JwkFetchSettings {
+        refresh_interval: Duration::from_secs(10),
+        max_refresh_interval: Duration::from_secs(3600),
+        max_backoff: Duration::from_secs(300),
+        grace_period: Duration::from_secs(3600),
+    };
+    assert_eq!(settings.refresh_delay(None), Duration::from_secs(10));
+    assert_eq!(
+        settings.refresh_delay(Some(Duration::from_secs(0))),
+        Duration::from_secs(10)
+    );
+    assert_eq!(
+        settings.refresh_delay(Some(Duration::from_secs(600))),
+        Duration::from_secs(600)
+    );
+    assert_eq!(
+        settings.refresh_delay(Some(Duration::from_secs(21600))),
+        Duration::from_secs(3600)
+    );
+
+    assert_eq!(settings.backoff_delay(1), Duration::from_secs(10));
+    assert_eq!(settings.backoff_delay(2), Duration::from_secs(20));
+    assert_eq!(settings.backoff_delay(5), Duration::from_secs(160));
+    assert_eq!(settings.backoff_delay(6), Duration::from_secs(300));
+    assert_eq!(settings.backoff_delay(100), Duration::from_secs(300));
+}
+
+/// A JWK endpoint that serves `kids`, with an `ETag` that changes whenever they do.
+struct MockJwkEndpoint {
+    kids: Mutex<Vec<String>>,
+    discovery_responses: AtomicUsize,
+    full_responses: AtomicUsize,
+    not_modified_responses: AtomicUsize,
+}
+
+impl MockJwkEndpoint {
+    fn etag(kids: &[String]) -> String {
+        format!("\"{}\"", kids.join("-"))
+    }
+}
+
+fn mock_jwk(kid: &str) -> Value {
+    json!({"kty": "RSA", "kid": kid, "alg": "RS256", "e": "AQAB", "n": "AQAB"})
+}
+
+/// Serves `endpoint` at `/jwks`, and an OIDC discovery document that points there. Returns the
+/// base URL, which is also the issuer of the discovery document.
+async fn spawn_mock_jwk_endpoint(endpoint: Arc<MockJwkEndpoint>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let discovery_document = json!({
+        "issuer": base_url,
+        "jwks_uri": format!("{base_url}/jwks"),
+    });
+
+    let discovery_endpoint = endpoint.clone();
+    let app = Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(move || async move {
+                discovery_endpoint
+                    .discovery_responses
+                    .fetch_add(1, Ordering::SeqCst);
+                Json(discovery_document)
+            }),
+        )
+        .route(
+            "/jwks",
+            get(move |headers: HeaderMap| async move {
+                let kids = endpoint.kids.lock().unwrap().clone();
+                let etag = MockJwkEndpoint::etag(&kids);
+                let if_none_match = headers
+                    .get(header::IF_NONE_MATCH)
+                    .and_then(|v| v.to_str().ok());
+                if if_none_match == Some(etag.as_str()) {
+                    endpoint
+                        .not_modified_responses
+                        .fetch_add(1, Ordering::SeqCst);
+                    return StatusCode::NOT_MODIFIED.into_response();
+                }
+                endpoint.full_responses.fetch_add(1, Ordering::SeqCst);
+                let keys: Vec<Value> = kids.iter().map(|kid| mock_jwk(kid)).collect();
+                (
+                    [
+                        (header::ETAG, etag),
+                        (header::CACHE_CONTROL, String::from("max-age=0")),
+                    ],
+                    Json(json!({ "keys": keys })),
+                )
+                    .into_response()
+            }),
+        );
+
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base_url
+}
+
+fn mock_endpoint_serving(kid: &str) -> Arc<MockJwkEndpoint> {
+    Arc::new(MockJwkEndpoint {
+        kids: Mutex::new(vec![String::from(kid)]),
+        discovery_responses: AtomicUsize::new(0),
+        full_responses: AtomicUsize::new(0),
+        not_modified_responses: AtomicUsize::new(0),
+    })
+}
+
+fn test_provider(iss: &str, endpoint_url: Option<String>) -> OidcProvider {
+    OidcProvider {
+        iss: String::from(iss),
+        endpoint_url,
+        fetch_timeout_secs: 5,
+        uid_keys: vec![],
+        allowed_auds: vec![],
+        max_exp_horizon_secs: None,
+        iat_leeway_secs: 0,
+        exp_leeway_secs: 0,
+        require_email_verified: false,
+        allow_skip_aud_checks: true,
+    }
+}
+
+/// Wait until `condition` holds, failing if it does not within a deadline that is far longer than
+/// the test's refresh interval, so that slow CI machines do not make the test flaky.
+async fn eventually(what: &str, condition: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting until {what}");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_jwk_refresh_is_conditional_and_keeps_rotated_keys_for_a_while() {
+    let iss = "jwk.test.rotation";
+    let endpoint = mock_endpoint_serving("k1");
+    let base_url = spawn_mock_jwk_endpoint(endpoint.clone()).await;
+    let provider = test_provider(iss, Some(format!("{base_url}/jwks")));
+    let grace_period = Duration::from_secs(2);
+    let settings = test_fetch_settings(grace_period);
+
+    update_jwk_fetching(&[provider], settings).await;
+    assert!(cached_decoding_key(iss, "k1").is_ok());
+
+    eventually("the JWKs are refreshed", || {
+        endpoint.not_modified_responses.load(Ordering::SeqCst) > 0
+    })
+    .await;
+    assert_eq!(endpoint.full_responses.load(Ordering::SeqCst), 1);
+
+    // Rotate k1 out: it is still accepted during the grace period, and dropped after. The grace
+    // period starts once the fetcher sees the rotation, so no later than `rotated_at`.
+    let rotated_at = Instant::now();
+    *endpoint.kids.lock().unwrap() = vec![String::from("k2")];
+    eventually("k2 is cached", || cached_decoding_key(iss, "k2").is_ok()).await;
+    let k1_accepted = cached_decoding_key(iss, "k1").is_ok();
+    assert!(k1_accepted || rotated_at.elapsed() >= grace_period);
+
+    eventually("k1 is dropped", || cached_decoding_key(iss, "k1").is_err()).await;
+    assert!(cached_decoding_key(iss, "k2").is_ok());
+
+    update_jwk_fetching(&[], settings).await;
+    assert!(cached_decoding_key(iss, "k2").is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_jwk_endpoint_is_discovered_from_iss() {
+    let iss = spawn_mock_jwk_endpoint(mock_endpoint_serving("k1")).await;
+    let settings = test_fetch_settings(Duration::from_secs(1));
+
+    update_jwk_fetching(&[test_provider(&iss, None)], settings).await;
+    assert!(cached_decoding_key(&iss, "k1").is_ok());
+
+    update_jwk_fetching(&[], settings).await;
+}
+
+#[tokio::test]
+async fn test_discovery_document_must_be_for_the_iss() {
+    let base_url = spawn_mock_jwk_endpoint(mock_endpoint_serving("k1")).await;
+    let client = reqwest::Client::new();
+
+    assert_eq!(
+        discover_jwks_uri(&client, &base_url).await.unwrap(),
+        format!("{base_url}/jwks")
+    );
+    assert!(discover_jwks_uri(&client, &format!("{base_url}/"))
+        .await
+        .is_err());
+}
+
+fn issuer_patterns(patterns: &[&str]) -> Vec<IssuerPattern> {
+    patterns
+        .iter()
+        .map(|pattern| IssuerPattern::try_from(String::from(*pattern)).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_federated_issuer_patterns_match_whole_iss() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default());
+    let patterns = &testcase.prover_service_config.federated_issuer_patterns;
+
+    assert!(is_federated_issuer(
+        patterns,
+        "https://dev-qtdgjv22jh0v1k7g.us.auth0.com/"
+    ));
+    assert!(is_federated_issuer(
+        patterns,
+        "https://cognito-idp.us-east-1.amazonaws.com/pool"
+    ));
+    assert!(!is_federated_issuer(
+        patterns,
+        "https://dev.us.auth0.com/evil"
+    ));
+    assert!(!is_federated_issuer(
+        patterns,
+        "https://evil.com/https://dev.us.auth0.com/"
+    ));
+}
+
+#[test]
+fn test_invalid_federated_issuer_patterns_fail_the_config_load() {
+    assert!(IssuerPattern::try_from(String::from("(")).is_err());
+
+    let config = |patterns: &str| {
+        Figment::new()
+            .merge(Yaml::file(config::LOCAL_TESTING_CONFIG_FILE_PATH))
+            .merge(Yaml::string(&format!(
+                "federated_issuer_patterns: {patterns}"
+            )))
+            .extract::<ProverServiceConfig>()
+    };
+    let loaded = config(r"['https://[a-z]+\.example\.com/']").unwrap();
+    assert!(is_federated_issuer(
+        &loaded.federated_issuer_patterns,
+        "https://tenant.example.com/"
+    ));
+    assert!(config("['(']").is_err());
+}
+
+#[test]
+fn test_federated_jwks_uri_must_be_on_the_issuers_https_origin() {
+    let patterns = issuer_patterns(&[r"https://[a-z]+\.us\.auth0\.com/"]);
+    let iss = "https://tenant.us.auth0.com/";
+
+    assert!(check_federated_jwks_uri(
+        &patterns,
+        iss,
+        "https://tenant.us.auth0.com/.well-known/jwks.json"
+    )
+    .is_ok());
+    for jwks_uri in [
+        "http://tenant.us.auth0.com/.well-known/jwks.json",
+        "https://tenant.us.auth0.com:8443/.well-known/jwks.json",
+        "https://other.us.auth0.com/.well-known/jwks.json",
+        "https://169.254.169.254/latest/meta-data",
+        "http://localhost:9101/admin/reload_config",
+        "not a url",
+    ] {
+        assert!(check_federated_jwks_uri(&patterns, iss, jwks_uri).is_err());
+    }
+
+    // JWK endpoints elsewhere can be allowed with a pattern.
+    let patterns = issuer_patterns(&[
+        r"https://[a-z]+\.us\.auth0\.com/",
+        r"https://keys\.example\.com/[a-z]+",
+    ]);
+    assert!(check_federated_jwks_uri(&patterns, iss, "https://keys.example.com/tenant").is_ok());
+}
+
+#[tokio::test]
+async fn test_federated_jwk_fetch_from_configured_pattern() {
+    let endpoint = mock_endpoint_serving("k1");
+    let iss = spawn_mock_jwk_endpoint(endpoint.clone()).await;
+    let jwt_payload = TestJWTPayload {
+        iss: iss.clone(),
+        ..TestJWTPayload::default()
+    };
+    let testcase = ProofTestCase::default_with_payload(jwt_payload).compute_nonce();
+    let jwt_with_kid = |kid: &str| {
+        let jwk_keypair = gen_test_jwk_keypair_with_kid_override(kid);
+        let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
+        DecodedJWT::from_b64(&prover_request_input.jwt_b64).unwrap()
+    };
+    let jwt = jwt_with_kid("k1");
+
+    assert!(get_federated_jwk(&[], &jwt).await.is_err());
+    let patterns = issuer_patterns(&[r"http://127\.0\.0\.1:\d+(/jwks)?"]);
+    assert!(get_federated_jwk(&patterns, &jwt).await.is_ok());
+    assert!(get_federated_jwk(&patterns, &jwt).await.is_ok());
+    assert_eq!(endpoint.discovery_responses.load(Ordering::SeqCst), 1);
+    assert_eq!(endpoint.full_responses.load(Ordering::SeqCst), 1);
+
+    // An unknown kid does not make every request fetch the JWKs again.
+    assert!(get_federated_jwk(&patterns, &jwt_with_kid("k2"))
+        .await
+        .is_err());
+    assert_eq!(endpoint.full_responses.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_federated_jwk_fetch_failures_are_cached() {
+    let endpoint = mock_endpoint_serving("k1");
+    let iss = spawn_mock_jwk_endpoint(endpoint.clone()).await;
+    let jwt_payload = TestJWTPayload {
+        iss,
+        ..TestJWTPayload::default()
+    };
+    let testcase = ProofTestCase::default_with_payload(jwt_payload).compute_nonce();
+    let jwk_keypair = gen_test_jwk_keypair_with_kid_override("k1");
+    let prover_request_input = testcase.convert_to_prover_request(&jwk_keypair);
+    let jwt = DecodedJWT::from_b64(&prover_request_input.jwt_b64).unwrap();
+    // The mock's JWK endpoint is plain HTTP, so it must be allowed by a pattern too.
+    let patterns = issuer_patterns(&[r"http://127\.0\.0\.1:\d+"]);
+
+    for _ in 0..3 {
+        let error_message = get_federated_jwk(&patterns, &jwt)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error_message.contains("is not on the HTTPS origin"));
+    }
+    assert_eq!(endpoint.discovery_responses.load(Ordering::SeqCst), 1);
+    assert_eq!(endpoint.full_responses.load(Ordering::SeqCst), 0);
+}
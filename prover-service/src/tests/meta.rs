@@ -0,0 +1,69 @@
+// Copyright © Aptos Foundation
+
+use crate::api::IssuerInfo;
+use crate::handlers::service_metadata;
+use crate::tests::common::{
+    init_test_state,
+    types::{ProofTestCase, TestJWTPayload},
+};
+use aptos_crypto::ValidCryptoMaterialStringExt;
+use serial_test::serial;
+
+#[tokio::test]
+#[serial]
+async fn meta_lists_what_clients_need() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let (state, _, tw_pk) = init_test_state(&testcase);
+
+    let metadata = service_metadata(&state);
+    assert_eq!(
+        metadata.training_wheels.pubkey,
+        tw_pk.to_encoded_string().unwrap()
+    );
+    assert_eq!(
+        metadata.training_wheels.on_chain_keyless_configuration,
+        state.tw_keys.get().active().on_chain_repr
+    );
+    assert_eq!(metadata.default_uid_keys, vec!["sub", "email"]);
+
+    let setup = state.setups.default_setup();
+    assert_eq!(metadata.setups.len(), 1);
+    assert_eq!(metadata.setups[0].name, setup.name);
+    assert_eq!(metadata.setups[0].vk_hash, setup.vk_hash);
+    assert_eq!(
+        metadata.setups[0].max_lengths,
+        setup.circuit_config.max_lengths
+    );
+    assert_eq!(
+        metadata.issuers,
+        state
+            .config()
+            .oidc_providers
+            .iter()
+            .map(|provider| IssuerInfo {
+                iss: provider.iss.clone(),
+                policy: provider.policy(),
+            })
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn meta_leaves_out_internal_settings() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let (state, _, _) = init_test_state(&testcase);
+
+    let json = serde_json::to_string(&service_metadata(&state)).unwrap();
+    for internal in [
+        "resources_dir",
+        "zkey_filename",
+        "enable_dangerous_logging",
+        "enable_debug_checks",
+        "use_insecure_jwk_for_test",
+        "api_keys",
+        "tw_keys_file",
+    ] {
+        assert!(!json.contains(internal), "/meta exposes {internal}");
+    }
+}
@@ -1,5 +1,20 @@
+pub mod auth;
 pub mod common;
+pub mod config_reload;
+pub mod error_codes;
+pub mod health;
+pub mod jobs;
 pub mod jwk_fetching;
+pub mod meta;
 pub mod playground;
+pub mod proof_cache;
+pub mod prove_batch;
+pub mod prover_pool;
+pub mod rate_limit;
+pub mod setup;
+pub mod shutdown;
 pub mod smoke;
+pub mod tls;
 pub mod training_wheels;
+pub mod tw_signer;
+pub mod validate;
@@ -0,0 +1,118 @@
+// Copyright © Aptos Foundation
+
+use crate::api::{ErrorCode, ProverServiceResponse};
+use crate::proof_cache::{ProofCache, ProofCacheKey};
+use aptos_crypto::HashValue;
+use aptos_types::keyless::{G1Bytes, G2Bytes, Groth16Proof};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn success_response() -> ProverServiceResponse {
+    let g1 = G1Bytes::new_unchecked("1", "2").unwrap();
+    let g2 = G2Bytes::new_unchecked(["1", "2"], ["3", "4"]).unwrap();
+    ProverServiceResponse::Success {
+        proof: Groth16Proof::new(g1, g2, g1),
+        public_inputs_hash: [7u8; 32],
+        training_wheels_signature: vec![1, 2, 3],
+    }
+}
+
+fn key(cache: &ProofCache, request: &[u8]) -> ProofCacheKey {
+    cache.key(
+        HashValue::sha3_256_of(request),
+        HashValue::sha3_256_of(b"vk"),
+    )
+}
+
+fn secs_from_now(secs: u64) -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        + secs
+}
+
+#[test]
+fn cache_returns_successful_responses() {
+    let cache = ProofCache::new(8, Duration::from_secs(60));
+    let cache_key = key(&cache, b"request");
+    assert!(cache.get(&cache_key).is_none());
+
+    cache.insert(cache_key, secs_from_now(3600), &success_response());
+    assert!(matches!(
+        cache.get(&cache_key),
+        Some(ProverServiceResponse::Success { .. })
+    ));
+}
+
+#[test]
+fn cache_skips_errors_and_expired_epks() {
+    let cache = ProofCache::new(8, Duration::from_secs(60));
+    let error_response = ProverServiceResponse::Error {
+        error_code: ErrorCode::ProverFailed,
+        message: String::from("prover failed"),
+    };
+
+    cache.insert(key(&cache, b"error"), secs_from_now(3600), &error_response);
+    cache.insert(
+        key(&cache, b"expired"),
+        secs_from_now(0),
+        &success_response(),
+    );
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn cache_entries_expire() {
+    let cache = ProofCache::new(8, Duration::from_millis(10));
+    let cache_key = key(&cache, b"request");
+    cache.insert(cache_key, secs_from_now(3600), &success_response());
+
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(cache.get(&cache_key).is_none());
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn cache_evicts_least_recently_used_entry() {
+    let cache = ProofCache::new(2, Duration::from_secs(60));
+    let first = key(&cache, b"first");
+    let second = key(&cache, b"second");
+    let third = key(&cache, b"third");
+
+    cache.insert(first, secs_from_now(3600), &success_response());
+    cache.insert(second, secs_from_now(3600), &success_response());
+    assert!(cache.get(&first).is_some());
+    cache.insert(third, secs_from_now(3600), &success_response());
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&first).is_some());
+    assert!(cache.get(&second).is_none());
+    assert!(cache.get(&third).is_some());
+}
+
+#[test]
+fn cache_entries_are_per_setup() {
+    let cache = ProofCache::new(8, Duration::from_secs(60));
+    let request_hash = HashValue::sha3_256_of(b"request");
+    let old_setup = cache.key(request_hash, HashValue::sha3_256_of(b"old vk"));
+    let new_setup = cache.key(request_hash, HashValue::sha3_256_of(b"new vk"));
+
+    cache.insert(old_setup, secs_from_now(3600), &success_response());
+    assert!(cache.get(&old_setup).is_some());
+    assert!(cache.get(&new_setup).is_none());
+}
+
+#[test]
+fn responses_admitted_before_a_clear_are_not_cached() {
+    let cache = ProofCache::new(8, Duration::from_secs(60));
+    let in_flight = key(&cache, b"request");
+
+    cache.clear();
+    cache.insert(in_flight, secs_from_now(3600), &success_response());
+    assert!(cache.is_empty());
+
+    let admitted_after = key(&cache, b"request");
+    assert_ne!(admitted_after, in_flight);
+    cache.insert(admitted_after, secs_from_now(3600), &success_response());
+    assert!(cache.get(&admitted_after).is_some());
+}
@@ -0,0 +1,104 @@
+// Copyright © Aptos Foundation
+
+use crate::api::{ErrorCode, ProverServiceResponse};
+use crate::config::RateLimit;
+use crate::handlers::prove_batch_handler;
+use crate::rate_limit::ClientIp;
+use crate::tests::common::{
+    init_test_state,
+    types::{ProofTestCase, TestJWTPayload},
+};
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use axum_extra::extract::WithRejection;
+use serial_test::serial;
+use std::{marker::PhantomData, net::IpAddr};
+
+#[tokio::test]
+#[serial]
+async fn batch_items_succeed_or_fail_independently() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    // Nonce not computed from the EPK, so this request fails the nonce check.
+    let bad_testcase = ProofTestCase::default_with_payload(TestJWTPayload::default());
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let batch = vec![
+        testcase.convert_to_prover_request(&jwk_keypair),
+        bad_testcase.convert_to_prover_request(&jwk_keypair),
+    ];
+
+    let Json(responses) =
+        prove_batch_handler(State(state), None, WithRejection(Json(batch), PhantomData))
+            .await
+            .unwrap();
+
+    assert_eq!(responses.len(), 2);
+    assert!(matches!(
+        responses[0],
+        ProverServiceResponse::Success { .. }
+    ));
+    assert!(matches!(
+        responses[1],
+        ProverServiceResponse::Error {
+            error_code: ErrorCode::NonceMismatch,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn batch_larger_than_max_size_is_rejected() {
+    let mut testcase =
+        ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    testcase.prover_service_config.max_prove_batch_size = 1;
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let batch = vec![
+        testcase.convert_to_prover_request(&jwk_keypair),
+        testcase.convert_to_prover_request(&jwk_keypair),
+    ];
+
+    let error = prove_batch_handler(State(state), None, WithRejection(Json(batch), PhantomData))
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.code(), StatusCode::BAD_REQUEST);
+    assert_eq!(error.error_code(), ErrorCode::InvalidRequest);
+}
+
+#[tokio::test]
+#[serial]
+async fn batch_items_are_charged_to_the_client_ip_rate_limit() {
+    let mut testcase =
+        ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    testcase.prover_service_config.rate_limit_per_client_ip = Some(RateLimit {
+        requests_per_minute: 1.0,
+        burst: 1,
+    });
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let ip: IpAddr = "10.0.0.1".parse().unwrap();
+    // The middleware charges the batch request itself, which pays for its first item.
+    assert!(state.rate_limiters.check_client_ip(ip).is_ok());
+    let batch = vec![
+        testcase.convert_to_prover_request(&jwk_keypair),
+        testcase.convert_to_prover_request(&jwk_keypair),
+    ];
+
+    let Json(responses) = prove_batch_handler(
+        State(state),
+        Some(Extension(ClientIp(ip))),
+        WithRejection(Json(batch), PhantomData),
+    )
+    .await
+    .unwrap();
+
+    assert!(matches!(
+        responses[0],
+        ProverServiceResponse::Success { .. }
+    ));
+    assert!(matches!(
+        responses[1],
+        ProverServiceResponse::Error {
+            error_code: ErrorCode::RateLimited,
+            ..
+        }
+    ));
+}
@@ -0,0 +1,168 @@
+// Copyright © Aptos Foundation
+
+use crate::handlers::encode_proof;
+use crate::load_vk::prepared_vk;
+use crate::metrics;
+use crate::prover_pool::{AdmissionError, PoolLabels, ProverPool};
+use std::{sync::Arc, time::Duration};
+
+const TOY_ZKEY_PATH: &str = "./resources/toy_circuit/toy_1.zkey";
+const TOY_WITNESS_PATH: &str = "./resources/toy_circuit/toy.wtns";
+const TOY_VK_PATH: &str = "./resources/toy_circuit/toy_vk.json";
+
+fn toy_pool_labels() -> PoolLabels {
+    PoolLabels {
+        setup: String::from("toy_circuit"),
+        vk_hash: String::from("toy_vk_hash"),
+    }
+}
+
+fn toy_pool(num_workers: usize) -> anyhow::Result<ProverPool> {
+    ProverPool::new(toy_pool_labels(), TOY_ZKEY_PATH, num_workers)
+}
+
+#[tokio::test]
+async fn pool_proves_concurrently() {
+    let pool = Arc::new(toy_pool(2).unwrap());
+    assert_eq!(pool.num_workers(), 2);
+
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                let worker = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+                worker.prove(String::from(TOY_WITNESS_PATH)).await
+            })
+        })
+        .collect();
+
+    let g16vk = prepared_vk(TOY_VK_PATH);
+    for task in tasks {
+        let output = task.await.unwrap().unwrap();
+        let proof = encode_proof(&serde_json::from_str(&output.proof_json).unwrap()).unwrap();
+        proof.verify_proof(2.into(), &g16vk).unwrap();
+    }
+
+    assert_eq!(pool.num_idle_workers(), 2);
+}
+
+#[tokio::test]
+async fn worker_owns_witness_tempfile_until_proving_finishes() {
+    let pool = toy_pool(1).unwrap();
+    let witness_file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::copy(TOY_WITNESS_PATH, witness_file.path()).unwrap();
+    let witness_path = witness_file.path().to_path_buf();
+
+    let worker = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+    let output = worker.prove(witness_file).await.unwrap();
+    let proof = encode_proof(&serde_json::from_str(&output.proof_json).unwrap()).unwrap();
+    proof
+        .verify_proof(2.into(), &prepared_vk(TOY_VK_PATH))
+        .unwrap();
+
+    // The witness is deleted once the worker is done with it.
+    assert!(!witness_path.exists());
+}
+
+#[tokio::test]
+async fn pool_hands_out_least_recently_used_worker() {
+    let pool = toy_pool(2).unwrap();
+
+    let first = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+    let second = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+    assert_ne!(first.worker_id(), second.worker_id());
+    assert_eq!(pool.num_idle_workers(), 0);
+
+    let first_id = first.worker_id();
+    drop(first);
+    drop(second);
+    let third = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+    assert_eq!(third.worker_id(), first_id);
+}
+
+#[tokio::test]
+async fn pool_rejects_requests_when_queue_is_full() {
+    let pool = toy_pool(1)
+        .unwrap()
+        .with_queue_limits(2, Duration::from_secs(60));
+
+    let first = pool.enqueue().unwrap();
+    let _second = pool.enqueue().unwrap();
+    assert!(matches!(
+        pool.enqueue(),
+        Err(AdmissionError::QueueFull { max_depth: 2 })
+    ));
+
+    // Getting a worker frees up a spot in the queue.
+    let _worker = pool.acquire(first).await.unwrap();
+    assert_eq!(pool.queue_depth(), 1);
+    assert!(pool.enqueue().is_ok());
+}
+
+#[tokio::test]
+async fn pool_times_out_requests_waiting_too_long() {
+    let pool = toy_pool(1)
+        .unwrap()
+        .with_queue_limits(8, Duration::from_millis(100));
+
+    let _worker = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+    assert!(matches!(
+        pool.acquire(pool.enqueue().unwrap()).await,
+        Err(AdmissionError::QueueTimeout { .. })
+    ));
+    assert_eq!(pool.queue_depth(), 0);
+}
+
+#[tokio::test]
+async fn pool_reports_busy_workers() {
+    let pool = toy_pool(2).unwrap();
+    assert!(pool.busy_workers().is_empty());
+
+    let worker = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+    let busy_workers = pool.busy_workers();
+    assert_eq!(busy_workers.len(), 1);
+    assert_eq!(busy_workers[0].0, worker.worker_id());
+
+    drop(worker);
+    assert!(pool.busy_workers().is_empty());
+}
+
+#[tokio::test]
+async fn worker_metrics_are_labeled_by_setup_and_removed_with_the_last_pool() {
+    let labels = PoolLabels {
+        setup: String::from("toy_circuit"),
+        vk_hash: String::from("dropped_pool_vk_hash"),
+    };
+    let busy = || {
+        metrics::PROVER_WORKER_BUSY
+            .get_metric_with_label_values(&["toy_circuit", "dropped_pool_vk_hash", "0"])
+            .unwrap()
+            .get()
+    };
+    let num_series = || {
+        prometheus::gather()
+            .iter()
+            .filter(|family| family.get_name() == "prover_worker_busy")
+            .flat_map(|family| family.get_metric())
+            .filter(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|label| label.get_value() == "dropped_pool_vk_hash")
+            })
+            .count()
+    };
+
+    let pool = ProverPool::new(labels.clone(), TOY_ZKEY_PATH, 1).unwrap();
+    let reloaded_pool = ProverPool::new(labels, TOY_ZKEY_PATH, 1).unwrap();
+    let worker = pool.acquire(pool.enqueue().unwrap()).await.unwrap();
+    assert_eq!(busy(), 1);
+    drop(worker);
+    assert_eq!(busy(), 0);
+
+    // The series is shared with the other pool for the same setup, so it stays until both are gone.
+    drop(pool);
+    assert_eq!(num_series(), 1);
+    drop(reloaded_pool);
+    assert_eq!(num_series(), 0);
+}
@@ -0,0 +1,141 @@
+// Copyright © Aptos Foundation
+
+use crate::api::ErrorCode;
+use crate::config::{IpRange, RateLimit};
+use crate::rate_limit::{client_ip, RateLimiter, RateLimiters};
+use crate::tests::common::get_config;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use std::{net::IpAddr, time::Duration};
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+fn ip_range(range: &str) -> IpRange {
+    IpRange::try_from(String::from(range)).unwrap()
+}
+
+#[test]
+fn bucket_allows_burst_then_rejects() {
+    let limiter = RateLimiter::new(RateLimit {
+        requests_per_minute: 6.0,
+        burst: 2,
+    });
+
+    assert!(limiter.check("client").is_ok());
+    assert!(limiter.check("client").is_ok());
+    let retry_after = limiter.check("client").unwrap_err();
+    assert!(retry_after > Duration::from_secs(9) && retry_after <= Duration::from_secs(10));
+
+    // Other keys have their own buckets.
+    assert!(limiter.check("other client").is_ok());
+    assert_eq!(limiter.num_tracked_keys(), 2);
+}
+
+#[test]
+fn bucket_refills_over_time() {
+    let limiter = RateLimiter::new(RateLimit {
+        requests_per_minute: 60_000.0,
+        burst: 1,
+    });
+
+    assert!(limiter.check("client").is_ok());
+    assert!(limiter.check("client").is_err());
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(limiter.check("client").is_ok());
+}
+
+#[test]
+fn rate_limited_requests_get_429() {
+    let mut config = get_config();
+    config.rate_limit_per_client_ip = Some(RateLimit {
+        requests_per_minute: 1.0,
+        burst: 1,
+    });
+    let rate_limiters = RateLimiters::from_config(&config);
+    let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+    assert!(rate_limiters.check_client_ip(ip).is_ok());
+    let error = rate_limiters.check_client_ip(ip).unwrap_err();
+    assert_eq!(error.code(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error.error_code(), ErrorCode::RateLimited);
+    assert_eq!(error.retry_after, Some(Duration::from_secs(60)));
+}
+
+#[test]
+fn unconfigured_limits_do_not_limit() {
+    let rate_limiters = RateLimiters::from_config(&get_config());
+    let ip: IpAddr = "10.0.0.1".parse().unwrap();
+    for _ in 0..100 {
+        assert!(rate_limiters.check_client_ip(ip).is_ok());
+    }
+}
+
+#[test]
+fn only_full_buckets_are_evicted() {
+    let slow_limiter = RateLimiter::new(RateLimit {
+        requests_per_minute: 1.0,
+        burst: 1,
+    });
+    let fast_limiter = RateLimiter::new(RateLimit {
+        requests_per_minute: 60_000.0,
+        burst: 1,
+    });
+    assert!(slow_limiter.check("client").is_ok());
+    assert!(fast_limiter.check("client").is_ok());
+
+    std::thread::sleep(Duration::from_millis(5));
+    slow_limiter.evict_full_buckets();
+    fast_limiter.evict_full_buckets();
+    assert_eq!(slow_limiter.num_tracked_keys(), 1);
+    assert_eq!(fast_limiter.num_tracked_keys(), 0);
+}
+
+#[test]
+fn ip_ranges_are_parsed_and_matched() {
+    let range = ip_range("10.0.0.0/8");
+    assert!(range.contains(ip("10.1.2.3")));
+    assert!(range.contains(ip("::ffff:10.1.2.3")));
+    assert!(!range.contains(ip("11.0.0.1")));
+
+    assert!(ip_range("192.168.1.1").contains(ip("192.168.1.1")));
+    assert!(!ip_range("192.168.1.1").contains(ip("192.168.1.2")));
+    assert!(ip_range("0.0.0.0/0").contains(ip("8.8.8.8")));
+    assert!(ip_range("fd00::/8").contains(ip("fd12::1")));
+    assert!(!ip_range("fd00::/8").contains(ip("10.0.0.1")));
+
+    for invalid in ["10.0.0.0/33", "10.0.0.0/x", "not an ip", "::/129"] {
+        assert!(
+            IpRange::try_from(String::from(invalid)).is_err(),
+            "{invalid}"
+        );
+    }
+    let config: Vec<IpRange> = serde_json::from_str(r#"["10.0.0.0/8", "::1"]"#).unwrap();
+    assert_eq!(config, vec![ip_range("10.0.0.0/8"), ip_range("::1/128")]);
+}
+
+#[test]
+fn client_ip_is_taken_from_x_forwarded_for_only_behind_trusted_proxies() {
+    let trusted_proxies = [ip_range("10.0.0.0/8")];
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "x-forwarded-for",
+        HeaderValue::from_static("6.6.6.6, 1.2.3.4, 10.0.0.2"),
+    );
+
+    // The client can prepend anything; only the addresses added by trusted proxies count.
+    assert_eq!(
+        client_ip(ip("10.0.0.1"), &headers, &trusted_proxies),
+        ip("1.2.3.4")
+    );
+    // Untrusted peers cannot pick their address.
+    assert_eq!(
+        client_ip(ip("5.5.5.5"), &headers, &trusted_proxies),
+        ip("5.5.5.5")
+    );
+    assert_eq!(client_ip(ip("10.0.0.1"), &headers, &[]), ip("10.0.0.1"));
+    assert_eq!(
+        client_ip(ip("10.0.0.1"), &HeaderMap::new(), &trusted_proxies),
+        ip("10.0.0.1")
+    );
+}
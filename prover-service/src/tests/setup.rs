@@ -0,0 +1,137 @@
+// Copyright © Aptos Foundation
+
+use crate::config::ProverServiceConfig;
+use crate::setup::{read_public_input, CircuitSetup, Setups};
+use crate::tests::common::get_config;
+use std::sync::Arc;
+
+fn toy_setup_config() -> ProverServiceConfig {
+    ProverServiceConfig {
+        resources_dir: String::from("./resources"),
+        setup_dir: String::from("toy_circuit"),
+        zkey_filename: String::from("toy_1.zkey"),
+        test_verification_key_filename: String::from("toy_vk.json"),
+        setup_self_test_witness_filename: String::from("toy.wtns"),
+        num_prover_workers: 1,
+        ..get_config()
+    }
+}
+
+#[test]
+fn reads_public_input_from_witness() {
+    let public_input = read_public_input("./resources/toy_circuit/toy.wtns").unwrap();
+    assert_eq!(public_input, 2.into());
+}
+
+#[test]
+fn reading_public_input_from_non_witness_fails() {
+    assert!(read_public_input("./resources/toy_circuit/toy_vk.json").is_err());
+}
+
+#[tokio::test]
+async fn setup_passes_self_test() {
+    let setup = CircuitSetup::load(&toy_setup_config()).unwrap();
+    setup.self_test().await.unwrap();
+    assert_eq!(setup.num_in_flight_proofs(), 0);
+}
+
+#[tokio::test]
+async fn setup_with_mismatched_vk_fails_self_test() {
+    let config = ProverServiceConfig {
+        test_verification_key_filename: String::from("../202405_vk.vkey"),
+        ..toy_setup_config()
+    };
+    let setup = CircuitSetup::load(&config).unwrap();
+    assert!(setup.self_test().await.is_err());
+}
+
+#[tokio::test]
+async fn setup_without_self_test_witness_or_input_fails_self_test() {
+    let config = ProverServiceConfig {
+        setup_self_test_witness_filename: String::from("missing.wtns"),
+        setup_self_test_input_path: None,
+        ..toy_setup_config()
+    };
+    let setup = CircuitSetup::load(&config).unwrap();
+    let err = setup.self_test().await.unwrap_err();
+    assert!(err.to_string().contains("setup_self_test_input_path"));
+}
+
+#[test]
+fn setup_with_missing_files_fails_to_load() {
+    let config = ProverServiceConfig {
+        zkey_filename: String::from("missing.zkey"),
+        ..toy_setup_config()
+    };
+    assert!(CircuitSetup::load(&config).is_err());
+}
+
+/// The toy setup, and a copy of it under another name and with the 2024-05 VK.
+fn two_setups() -> Setups {
+    let toy = CircuitSetup::load(&toy_setup_config()).unwrap();
+    let other = CircuitSetup {
+        name: String::from("other"),
+        ..CircuitSetup::load(&ProverServiceConfig {
+            test_verification_key_filename: String::from("../202405_vk.vkey"),
+            ..toy_setup_config()
+        })
+        .unwrap()
+    };
+    Setups::new(vec![toy, other])
+}
+
+#[test]
+fn setups_are_selected_by_name_or_vk_hash() {
+    let setups = two_setups();
+    assert_eq!(setups.select(None).unwrap().name, "toy_circuit");
+    assert_eq!(setups.select(Some("other")).unwrap().name, "other");
+
+    let vk_hash = "91d3eacd6b9cd732b9fdb7dfa918d14306609beb6ae08a774840272774cc83e2";
+    assert_eq!(setups.select(Some(vk_hash)).unwrap().name, "other");
+    assert_eq!(
+        setups.select(Some(&format!("0x{vk_hash}"))).unwrap().name,
+        "other"
+    );
+    assert!(setups.select(Some("missing")).is_none());
+
+    let infos = setups.infos();
+    assert_eq!(infos.len(), 2);
+    assert!(infos[0].is_default && !infos[1].is_default);
+    assert_eq!(infos[1].vk_hash.to_hex(), vk_hash);
+}
+
+#[test]
+fn swapped_out_setup_stays_live_while_in_use() {
+    let setups = two_setups();
+    let old = setups.default_setup();
+    let queue_ticket = old.prover_pool.enqueue().unwrap();
+
+    let new = setups
+        .swap(CircuitSetup::load(&toy_setup_config()).unwrap())
+        .unwrap();
+    assert!(Arc::ptr_eq(&setups.default_setup(), &new));
+    assert_eq!(setups.select(Some("other")).unwrap().name, "other");
+    assert_eq!(setups.live().len(), 3);
+    assert_eq!(
+        setups
+            .live()
+            .iter()
+            .map(|setup| setup.num_in_flight_proofs())
+            .sum::<usize>(),
+        1
+    );
+
+    drop(queue_ticket);
+    drop(old);
+    assert_eq!(setups.live().len(), 2);
+}
+
+#[test]
+fn swapping_in_unknown_setup_fails() {
+    let setups = two_setups();
+    let unknown = CircuitSetup {
+        name: String::from("unknown"),
+        ..CircuitSetup::load(&toy_setup_config()).unwrap()
+    };
+    assert!(setups.swap(unknown).is_err());
+}
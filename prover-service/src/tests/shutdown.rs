@@ -0,0 +1,59 @@
+// Copyright © Aptos Foundation
+
+use crate::api::ErrorCode;
+use crate::handlers::prove_handler;
+use crate::health;
+use crate::shutdown::{drain, num_in_flight_proofs};
+use crate::tests::common::{
+    init_test_state,
+    types::{ProofTestCase, TestJWTPayload},
+};
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::extract::WithRejection;
+use serial_test::serial;
+use std::{marker::PhantomData, time::Duration};
+
+#[tokio::test]
+#[serial]
+async fn shutdown_refuses_new_requests_and_fails_readiness() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    assert!(drain(&state, Duration::from_secs(1)).await);
+    assert!(state.is_shutting_down());
+
+    let error = prove_handler(
+        State(state.clone()),
+        WithRejection(Json(request), PhantomData),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error.code(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(error.error_code(), ErrorCode::ShuttingDown);
+
+    let report = health::readiness(&state);
+    assert!(!report.ok);
+    assert!(report
+        .checks
+        .iter()
+        .any(|check| check.name == "not_shutting_down" && !check.ok));
+}
+
+#[tokio::test]
+#[serial]
+async fn drain_waits_for_in_flight_proofs() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let (state, _, _) = init_test_state(&testcase);
+
+    let queue_ticket = state.setups.default_setup().prover_pool.enqueue().unwrap();
+    assert_eq!(num_in_flight_proofs(&state), 1);
+    assert!(!drain(&state, Duration::from_millis(200)).await);
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(queue_ticket);
+    });
+    assert!(drain(&state, Duration::from_secs(10)).await);
+    assert_eq!(num_in_flight_proofs(&state), 0);
+}
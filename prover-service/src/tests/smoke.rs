@@ -1,5 +1,6 @@
 // Copyright © Aptos Foundation
 
+use crate::config::TwSigningMode;
 use crate::handlers::encode_proof;
 use crate::load_vk::prepared_vk;
 use crate::tests::common::{
@@ -17,6 +18,16 @@ async fn default_request() {
     convert_prove_and_verify(&testcase).await.unwrap();
 }
 
+#[tokio::test]
+#[serial]
+async fn request_signed_with_vk_hash() {
+    let mut testcase =
+        ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    testcase.prover_service_config.training_wheels_signing_mode = TwSigningMode::V1;
+
+    convert_prove_and_verify(&testcase).await.unwrap();
+}
+
 #[tokio::test]
 #[serial]
 async fn request_with_email() {
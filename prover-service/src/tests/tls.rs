@@ -0,0 +1,135 @@
+// Copyright © Aptos Foundation
+
+use crate::config::TlsConfig;
+use crate::tls::{rustls_server_config, ClientCertAcceptor, ClientCertificate};
+use crate::watcher::FileWatcher;
+use axum::{routing::get, Extension, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use std::{fs, sync::Arc};
+
+const TEST_TLS_DIR: &str = "./resources/test_tls";
+
+fn test_tls_config(client_ca_path: Option<String>) -> TlsConfig {
+    TlsConfig {
+        cert_path: format!("{TEST_TLS_DIR}/server.pem"),
+        key_path: format!("{TEST_TLS_DIR}/server.key"),
+        client_ca_path,
+        reload_poll_secs: 1,
+    }
+}
+
+#[test]
+fn server_config_offers_http2() {
+    let config = rustls_server_config(&test_tls_config(None)).unwrap();
+    assert_eq!(
+        config.alpn_protocols,
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    );
+}
+
+#[test]
+fn server_config_loads_client_cas() {
+    let client_ca_path = Some(format!("{TEST_TLS_DIR}/ca.pem"));
+    assert!(rustls_server_config(&test_tls_config(client_ca_path)).is_ok());
+}
+
+#[test]
+fn server_config_fails_on_missing_files() {
+    let mut tls = test_tls_config(None);
+    tls.key_path = format!("{TEST_TLS_DIR}/missing.key");
+    assert!(rustls_server_config(&tls).is_err());
+}
+
+#[test]
+fn file_watcher_detects_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cert.pem");
+    let mut watcher = FileWatcher::new(vec![path.clone()]);
+    assert!(!watcher.poll_changed());
+
+    fs::write(&path, "first").unwrap();
+    assert!(watcher.poll_changed());
+    assert!(!watcher.poll_changed());
+
+    fs::remove_file(&path).unwrap();
+    assert!(watcher.poll_changed());
+}
+
+#[tokio::test]
+async fn serves_https() {
+    let rustls_config = RustlsConfig::from_config(Arc::new(
+        rustls_server_config(&test_tls_config(None)).unwrap(),
+    ));
+    let handle = axum_server::Handle::new();
+    let app = Router::new().route("/healthcheck", get(|| async { "OK" }));
+    tokio::spawn(
+        axum_server::bind_rustls("127.0.0.1:0".parse().unwrap(), rustls_config)
+            .handle(handle.clone())
+            .serve(app.into_make_service()),
+    );
+    let addr = handle.listening().await.unwrap();
+
+    let ca = fs::read(format!("{TEST_TLS_DIR}/ca.pem")).unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap())
+        .build()
+        .unwrap();
+    let body = client
+        .get(format!("https://localhost:{}/healthcheck", addr.port()))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body, "OK");
+}
+
+#[tokio::test]
+async fn client_certificates_are_optional_in_the_handshake() {
+    let client_ca_path = Some(format!("{TEST_TLS_DIR}/client_ca.pem"));
+    let rustls_config = RustlsConfig::from_config(Arc::new(
+        rustls_server_config(&test_tls_config(client_ca_path)).unwrap(),
+    ));
+    let handle = axum_server::Handle::new();
+    let app = Router::new().route(
+        "/client_certificate",
+        get(
+            |Extension(client_certificate): Extension<ClientCertificate>| async move {
+                client_certificate.verified.to_string()
+            },
+        ),
+    );
+    tokio::spawn(
+        axum_server::bind("127.0.0.1:0".parse().unwrap())
+            .acceptor(ClientCertAcceptor::new(rustls_config))
+            .handle(handle.clone())
+            .serve(app.into_make_service()),
+    );
+    let url = format!(
+        "https://localhost:{}/client_certificate",
+        handle.listening().await.unwrap().port()
+    );
+
+    let ca = reqwest::Certificate::from_pem(&fs::read(format!("{TEST_TLS_DIR}/ca.pem")).unwrap())
+        .unwrap();
+    let identity = reqwest::Identity::from_pkcs8_pem(
+        &fs::read(format!("{TEST_TLS_DIR}/client.pem")).unwrap(),
+        &fs::read(format!("{TEST_TLS_DIR}/client.key")).unwrap(),
+    )
+    .unwrap();
+    let anonymous_client = reqwest::Client::builder()
+        .add_root_certificate(ca.clone())
+        .build()
+        .unwrap();
+    let authenticated_client = reqwest::Client::builder()
+        .add_root_certificate(ca)
+        .identity(identity)
+        .build()
+        .unwrap();
+
+    for (client, verified) in [(anonymous_client, "false"), (authenticated_client, "true")] {
+        let body = client.get(&url).send().await.unwrap().text().await.unwrap();
+        assert_eq!(body, verified);
+    }
+}
@@ -1,11 +1,27 @@
-use crate::config::ProverServiceConfig;
+use crate::api::ErrorCode;
 use crate::config::CONFIG;
+use crate::config::{IssuerPolicy, ProverServiceConfig};
+use crate::error::CodedError;
 use crate::tests::common::types::{ProofTestCase, TestJWTPayload};
 use crate::tests::common::{gen_test_jwk_keypair, types::TestJWKKeyPair};
 use crate::training_wheels::validate_jwt_sig;
+use crate::training_wheels::verification_logic::{
+    check_jwt_alg_matches_jwk, check_jwt_header, circuit_supports,
+};
+use aptos_keyless_common::input_processing::encoding::{JwtAud, JwtPayload};
+use jsonwebtoken::Algorithm;
+use serde_json::{json, Value};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 fn test_jwt_validation(jwt_payload: TestJWTPayload, config: &ProverServiceConfig) {
+    test_jwt_validation_with_policy(jwt_payload, config, &IssuerPolicy::default());
+}
+
+fn test_jwt_validation_with_policy(
+    jwt_payload: TestJWTPayload,
+    config: &ProverServiceConfig,
+    policy: &IssuerPolicy,
+) {
     let testcase = ProofTestCase::default_with_payload(jwt_payload).compute_nonce();
 
     let jwk_keypair = gen_test_jwk_keypair();
@@ -14,6 +30,7 @@ fn test_jwt_validation(jwt_payload: TestJWTPayload, config: &ProverServiceConfig
         &jwk_keypair.into_rsa_jwk(),
         &prover_request_input.jwt_b64,
         config,
+        policy,
     )
     .is_ok());
 }
@@ -75,3 +92,140 @@ fn test_validate_jwt_sig_and_dates_future_iat_can_be_disabled() {
     config.enable_jwt_iat_not_in_future_check = false;
     test_jwt_validation(jwt_payload, &config);
 }
+
+#[test]
+fn test_validate_jwt_sig_and_dates_expired_within_leeway() {
+    let start = SystemTime::now();
+    let since_the_epoch = start
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards");
+
+    let jwt_payload = TestJWTPayload {
+        exp: since_the_epoch.as_secs() - 100,
+        ..TestJWTPayload::default()
+    };
+
+    let policy = IssuerPolicy {
+        exp_leeway_secs: 200,
+        ..IssuerPolicy::default()
+    };
+    test_jwt_validation_with_policy(jwt_payload, &CONFIG, &policy);
+}
+
+#[test]
+fn issuers_without_a_provider_get_the_federated_issuer_policy() {
+    let mut config = CONFIG.clone();
+    config.federated_issuer_policy = IssuerPolicy {
+        allow_skip_aud_checks: false,
+        ..IssuerPolicy::default()
+    };
+    assert_eq!(
+        config.issuer_policy("https://tenant.us.auth0.com/"),
+        config.federated_issuer_policy
+    );
+
+    let provider = &config.oidc_providers[0];
+    assert_eq!(config.issuer_policy(&provider.iss), provider.policy());
+    assert_eq!(IssuerPolicy::default().iat_leeway_secs, 0);
+}
+
+#[test]
+fn test_validate_jwt_sig_checks_alg_against_allowlist() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let jwk_keypair = gen_test_jwk_keypair();
+    let jwk = jwk_keypair.into_rsa_jwk();
+    let jwt = jwk_keypair.sign_with_alg(&testcase.jwt_payload, Algorithm::RS384);
+
+    assert!(validate_jwt_sig(&jwk, &jwt, &CONFIG, &IssuerPolicy::default()).is_err());
+
+    let mut config = CONFIG.clone();
+    config.allowed_jwt_algorithms = vec![Algorithm::RS256, Algorithm::RS384];
+    assert_eq!(
+        validate_jwt_sig(&jwk, &jwt, &config, &IssuerPolicy::default()).unwrap(),
+        Algorithm::RS384
+    );
+    assert!(!circuit_supports(Algorithm::RS384, &jwk));
+    assert!(circuit_supports(Algorithm::RS256, &jwk));
+}
+
+fn jwt_with_header(header: Value) -> String {
+    let header_b64 = base64::encode_config(header.to_string(), base64::URL_SAFE_NO_PAD);
+    format!("{header_b64}.e30.c2lnbmF0dXJl")
+}
+
+fn header_error_code(jwt: &str) -> ErrorCode {
+    check_jwt_header(jwt)
+        .unwrap_err()
+        .downcast::<CodedError>()
+        .unwrap()
+        .code
+}
+
+#[test]
+fn test_check_jwt_header() {
+    let header = check_jwt_header(&jwt_with_header(
+        json!({"alg": "RS256", "kid": "test-rsa", "typ": "JWT", "jku": "https://example.com"}),
+    ))
+    .unwrap();
+    assert_eq!(header.alg, "RS256");
+    assert_eq!(header.jku.as_deref(), Some("https://example.com"));
+
+    for header in [
+        json!({"alg": "RSA-OAEP", "enc": "A256GCM", "kid": "test-rsa"}),
+        json!({"alg": "none", "kid": "test-rsa"}),
+        json!({"kid": "test-rsa"}),
+        json!({"alg": "RS256"}),
+        json!({"alg": "RS256", "kid": "test-rsa", "typ": "at+jwt"}),
+        json!({"alg": "RS256", "kid": "test-rsa", "crit": ["b64"], "b64": false}),
+    ] {
+        assert_eq!(
+            header_error_code(&jwt_with_header(header)),
+            ErrorCode::JwtHeaderUnsupported
+        );
+    }
+
+    let unsigned = jwt_with_header(json!({"alg": "RS256", "kid": "test-rsa"}));
+    let unsigned = unsigned.trim_end_matches("c2lnbmF0dXJl");
+    assert_eq!(header_error_code(unsigned), ErrorCode::JwtHeaderUnsupported);
+    assert_eq!(
+        header_error_code("aGVhZGVy.cGF5bG9hZA.a2V5.aXY.dGFn"),
+        ErrorCode::JwtHeaderUnsupported
+    );
+    assert_eq!(
+        header_error_code("bm90IGpzb24.e30.c2ln"),
+        ErrorCode::JwtMalformed
+    );
+}
+
+#[test]
+fn test_check_jwt_alg_matches_jwk() {
+    let jwk = gen_test_jwk_keypair().into_rsa_jwk();
+    let header = |alg: &str| {
+        check_jwt_header(&jwt_with_header(json!({"alg": alg, "kid": "test-rsa"}))).unwrap()
+    };
+
+    assert!(check_jwt_alg_matches_jwk(&header("RS256"), &jwk).is_ok());
+    assert!(check_jwt_alg_matches_jwk(&header("RS384"), &jwk).is_err());
+}
+
+#[test]
+fn test_jwt_aud_can_be_an_array() {
+    let payload = |aud: Value| {
+        let json = json!({"iss": "test.oidc.provider", "iat": 0, "nonce": "", "aud": aud});
+        JwtPayload::from_b64(&base64::encode_config(
+            json.to_string(),
+            base64::URL_SAFE_NO_PAD,
+        ))
+        .unwrap()
+    };
+
+    let single = payload(json!("a"));
+    assert_eq!(single.aud, JwtAud::Single(String::from("a")));
+    assert_eq!(single.aud.as_single(), Some("a"));
+
+    let multiple = payload(json!(["a", "b"]));
+    assert!(multiple.aud.contains("b"));
+    assert!(!multiple.aud.contains("c"));
+    assert_eq!(multiple.aud.as_single(), None);
+    assert_eq!(multiple.aud.to_string(), "[a, b]");
+}
@@ -0,0 +1,239 @@
+// Copyright © Aptos Foundation
+
+use crate::config::{TwSignerConfig, TwSigningMode};
+use crate::tests::common::gen_test_training_wheels_keypair;
+use crate::training_wheels::{
+    self,
+    signer::{self, EncryptedKeyFile, InMemorySigner, RemoteSigner, TrainingWheelsSigner},
+    TrainingWheelsMessage,
+};
+use aptos_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
+    HashValue, ValidCryptoMaterialStringExt,
+};
+use aptos_types::keyless::{G1Bytes, G2Bytes, Groth16Proof};
+use axum::{http::HeaderMap, http::StatusCode, routing::post, Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{fs, sync::Arc, time::Duration};
+
+const AUTH_TOKEN: &str = "test-token";
+
+fn test_proof() -> Groth16Proof {
+    let g1 = G1Bytes::new_unchecked("1", "2").unwrap();
+    let g2 = G2Bytes::new_unchecked(["1", "2"], ["3", "4"]).unwrap();
+    Groth16Proof::new(g1, g2, g1)
+}
+
+fn test_vk_hash() -> HashValue {
+    HashValue::sha3_256_of(b"vk")
+}
+
+fn test_message(mode: TwSigningMode) -> TrainingWheelsMessage {
+    TrainingWheelsMessage::new(mode, test_proof(), [7u8; 32], &test_vk_hash())
+}
+
+async fn sign_and_verify(signer: &dyn TrainingWheelsSigner, pk: &Ed25519PublicKey) {
+    for mode in [TwSigningMode::V0, TwSigningMode::V1] {
+        let message = test_message(mode);
+        let signature = signer.sign(&message).await.unwrap();
+        message.verify(&signature, pk).unwrap();
+    }
+}
+
+#[derive(Deserialize)]
+struct MockSignRequest {
+    #[serde(with = "hex")]
+    message: Vec<u8>,
+}
+
+/// Serves the remote signer protocol, signing with `sk`. Returns the URL to sign at.
+async fn spawn_mock_signer(sk: Ed25519PrivateKey) -> String {
+    let sk = Arc::new(sk);
+    let app = Router::new().route(
+        "/sign",
+        post(
+            move |headers: HeaderMap, Json(request): Json<MockSignRequest>| async move {
+                let auth = headers.get("authorization").and_then(|v| v.to_str().ok());
+                if auth != Some(format!("Bearer {AUTH_TOKEN}").as_str()) {
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+                // The signing message is a 32-byte domain separator followed by the BCS-encoded
+                // statement; decode the statement back out so that the key can sign it.
+                let bcs_message = &request.message[32..];
+                let message = bcs::from_bytes(bcs_message)
+                    .map(TrainingWheelsMessage::V1)
+                    .or_else(|_| bcs::from_bytes(bcs_message).map(TrainingWheelsMessage::V0))
+                    .unwrap();
+                assert_eq!(message.signing_message().unwrap(), request.message);
+                let signature = training_wheels::sign(&sk, &message).unwrap();
+                Ok(Json::<Value>(
+                    json!({ "signature": hex::encode(signature.to_bytes()) }),
+                ))
+            },
+        ),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}/sign")
+}
+
+#[test]
+fn encrypted_key_file_needs_the_passphrase() {
+    let (sk, pk) = gen_test_training_wheels_keypair();
+    let encrypted_key = EncryptedKeyFile::encrypt(&sk, "passphrase").unwrap();
+
+    let decrypted = encrypted_key.decrypt("passphrase").unwrap();
+    assert_eq!(Ed25519PublicKey::from(&decrypted), pk);
+    assert!(encrypted_key.decrypt("wrong passphrase").is_err());
+}
+
+#[tokio::test]
+async fn signs_with_encrypted_key_file() {
+    let (sk, pk) = gen_test_training_wheels_keypair();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tw_key.json");
+    let passphrase_path = dir.path().join("passphrase");
+    fs::write(
+        &path,
+        serde_json::to_string(&EncryptedKeyFile::encrypt(&sk, "passphrase").unwrap()).unwrap(),
+    )
+    .unwrap();
+    fs::write(&passphrase_path, "passphrase\n").unwrap();
+
+    let signer = signer::from_config(&TwSignerConfig::EncryptedFile {
+        path: path.to_str().unwrap().to_owned(),
+        passphrase_path: passphrase_path.to_str().unwrap().to_owned(),
+    })
+    .unwrap();
+    assert_eq!(signer.public_key(), &pk);
+    sign_and_verify(signer.as_ref(), &pk).await;
+}
+
+#[tokio::test]
+async fn signs_with_remote_signer() {
+    let (sk, pk) = gen_test_training_wheels_keypair();
+    let url = spawn_mock_signer(sk).await;
+
+    let signer = RemoteSigner::new(
+        &url,
+        pk.clone(),
+        Some(String::from(AUTH_TOKEN)),
+        Duration::from_secs(5),
+    )
+    .unwrap();
+    sign_and_verify(&signer, &pk).await;
+}
+
+#[tokio::test]
+async fn remote_signer_fails_without_auth_token() {
+    let (sk, pk) = gen_test_training_wheels_keypair();
+    let url = spawn_mock_signer(sk).await;
+
+    let signer = RemoteSigner::new(&url, pk, None, Duration::from_secs(5)).unwrap();
+    assert!(signer.sign(&test_message(TwSigningMode::V0)).await.is_err());
+}
+
+#[tokio::test]
+async fn remote_signer_rejects_signatures_under_another_key() {
+    let (sk, _) = gen_test_training_wheels_keypair();
+    let (_, other_pk) = gen_test_training_wheels_keypair();
+    let url = spawn_mock_signer(sk).await;
+
+    let signer = RemoteSigner::new(
+        &url,
+        other_pk,
+        Some(String::from(AUTH_TOKEN)),
+        Duration::from_secs(5),
+    )
+    .unwrap();
+    assert!(signer.sign(&test_message(TwSigningMode::V0)).await.is_err());
+}
+
+/// Fixed signing messages and signatures under the testing TW key, in every signing mode, which
+/// the on-chain side can be tested against too. Each signing message is the domain separator
+/// `SHA3-256("APTOS::<statement type>")` followed by the BCS-encoded statement.
+#[derive(Deserialize)]
+struct TwSignatureTestVector {
+    mode: TwSigningMode,
+    public_key: String,
+    #[serde(with = "hex")]
+    proof: Vec<u8>,
+    #[serde(with = "hex")]
+    public_inputs_hash: Vec<u8>,
+    vk_hash: String,
+    #[serde(with = "hex")]
+    signing_message: Vec<u8>,
+    #[serde(with = "hex")]
+    signature: Vec<u8>,
+}
+
+fn tw_signature_test_vectors() -> Vec<TwSignatureTestVector> {
+    serde_json::from_str(&fs::read_to_string("./resources/tw_signature_test_vectors.json").unwrap())
+        .unwrap()
+}
+
+impl TwSignatureTestVector {
+    fn message(&self) -> TrainingWheelsMessage {
+        TrainingWheelsMessage::new(
+            self.mode,
+            bcs::from_bytes(&self.proof).unwrap(),
+            self.public_inputs_hash.clone().try_into().unwrap(),
+            &HashValue::from_hex(&self.vk_hash).unwrap(),
+        )
+    }
+}
+
+#[test]
+fn signing_messages_match_test_vectors() {
+    let vectors = tw_signature_test_vectors();
+    assert_eq!(
+        vectors.iter().map(|v| v.mode).collect::<Vec<_>>(),
+        [TwSigningMode::V0, TwSigningMode::V1]
+    );
+    for vector in vectors {
+        assert_eq!(
+            vector.message().signing_message().unwrap(),
+            vector.signing_message
+        );
+    }
+}
+
+#[test]
+fn signatures_match_test_vectors() {
+    let sk = Ed25519PrivateKey::from_encoded_string(
+        fs::read_to_string("private_key_for_testing.txt")
+            .unwrap()
+            .trim_end(),
+    )
+    .unwrap();
+    for vector in tw_signature_test_vectors() {
+        let pk = Ed25519PublicKey::from_encoded_string(&vector.public_key).unwrap();
+        assert_eq!(Ed25519PublicKey::from(&sk), pk);
+
+        let message = vector.message();
+        let signature = training_wheels::sign(&sk, &message).unwrap();
+        assert_eq!(signature.to_bytes().to_vec(), vector.signature);
+        message.verify(&signature, &pk).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn v1_signatures_are_bound_to_the_vk() {
+    let (sk, pk) = gen_test_training_wheels_keypair();
+    let signer = InMemorySigner::new(sk);
+    let signature = signer.sign(&test_message(TwSigningMode::V1)).await.unwrap();
+
+    let other_vk = TrainingWheelsMessage::new(
+        TwSigningMode::V1,
+        test_proof(),
+        [7u8; 32],
+        &HashValue::sha3_256_of(b"other vk"),
+    );
+    assert!(other_vk.verify(&signature, &pk).is_err());
+    assert!(test_message(TwSigningMode::V0)
+        .verify(&signature, &pk)
+        .is_err());
+}
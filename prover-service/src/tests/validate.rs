@@ -0,0 +1,276 @@
+// Copyright © Aptos Foundation
+
+use crate::api::ErrorCode;
+use crate::config::OidcProvider;
+use crate::handlers::validate_handler;
+use crate::tests::common::{
+    init_test_state,
+    types::{ProofTestCase, TestJWTPayload, WithNonce},
+};
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::extract::WithRejection;
+use serde::Serialize;
+use serde_json::json;
+use serial_test::serial;
+use std::{
+    marker::PhantomData,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[tokio::test]
+#[serial]
+async fn validate_reports_nonce_and_field_lengths() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    let Json(response) = validate_handler(State(state), WithRejection(Json(request), PhantomData))
+        .await
+        .unwrap();
+
+    assert_eq!(response.nonce, testcase.jwt_payload.nonce);
+    assert!(!response.field_lengths.is_empty());
+    assert!(response.field_lengths.iter().all(|length| length.fits()));
+    assert!(response
+        .field_lengths
+        .windows(2)
+        .all(|pair| pair[0].signal <= pair[1].signal));
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_rejects_fields_that_are_too_long() {
+    let jwt_payload = TestJWTPayload {
+        name: "a".repeat(1000),
+        ..TestJWTPayload::default()
+    };
+    let testcase = ProofTestCase::default_with_payload(jwt_payload).compute_nonce();
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    let error = validate_handler(State(state), WithRejection(Json(request), PhantomData))
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.error_code(), ErrorCode::JwtFieldTooLong);
+    assert_eq!(error.code(), StatusCode::BAD_REQUEST);
+}
+
+/// `testcase`, with `update` applied to the provider of its JWT's issuer.
+fn with_issuer_policy(
+    mut testcase: ProofTestCase<TestJWTPayload>,
+    update: impl Fn(&mut OidcProvider),
+) -> ProofTestCase<TestJWTPayload> {
+    for provider in &mut testcase.prover_service_config.oidc_providers {
+        if provider.iss == testcase.jwt_payload.iss {
+            update(provider);
+        }
+    }
+    testcase
+}
+
+fn testcase_with_uid_key(
+    uid_key: &str,
+    allowed_uid_keys: &[&str],
+) -> ProofTestCase<TestJWTPayload> {
+    let testcase = ProofTestCase {
+        uid_key: String::from(uid_key),
+        ..ProofTestCase::default_with_payload(TestJWTPayload::default())
+    }
+    .compute_nonce();
+    with_issuer_policy(testcase, |provider| {
+        provider.uid_keys = allowed_uid_keys
+            .iter()
+            .map(|key| String::from(*key))
+            .collect();
+    })
+}
+
+async fn validate_error_code(
+    testcase: ProofTestCase<impl Serialize + WithNonce + Clone>,
+) -> ErrorCode {
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    validate_handler(State(state), WithRejection(Json(request), PhantomData))
+        .await
+        .unwrap_err()
+        .error_code()
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_accepts_uid_keys_configured_for_the_issuer() {
+    let testcase = testcase_with_uid_key("given_name", &["sub", "given_name"]);
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    assert!(
+        validate_handler(State(state), WithRejection(Json(request), PhantomData))
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_rejects_uid_keys_not_configured_for_the_issuer() {
+    let testcase = testcase_with_uid_key("email", &["sub", "given_name"]);
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    let error = validate_handler(State(state), WithRejection(Json(request), PhantomData))
+        .await
+        .unwrap_err();
+    assert_eq!(error.error_code(), ErrorCode::UidKeyUnsupported);
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_rejects_missing_uid_claims() {
+    let testcase = testcase_with_uid_key("oid", &["sub", "oid"]);
+    let (state, jwk_keypair, _) = init_test_state(&testcase);
+    let request = testcase.convert_to_prover_request(&jwk_keypair);
+
+    let error = validate_handler(State(state), WithRejection(Json(request), PhantomData))
+        .await
+        .unwrap_err();
+    assert_eq!(error.error_code(), ErrorCode::UidClaimMissing);
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_enforces_allowed_auds() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let aud = testcase.jwt_payload.aud.clone();
+
+    let allowed = with_issuer_policy(testcase.clone(), |provider| {
+        provider.allowed_auds = vec![aud.clone()];
+    });
+    let (state, jwk_keypair, _) = init_test_state(&allowed);
+    let request = allowed.convert_to_prover_request(&jwk_keypair);
+    assert!(
+        validate_handler(State(state), WithRejection(Json(request), PhantomData))
+            .await
+            .is_ok()
+    );
+
+    let not_allowed = with_issuer_policy(testcase, |provider| {
+        provider.allowed_auds = vec![String::from("some-other-app")];
+    });
+    assert_eq!(
+        validate_error_code(not_allowed).await,
+        ErrorCode::IssuerPolicyViolation
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_enforces_max_exp_horizon() {
+    let testcase = ProofTestCase::default_with_payload(TestJWTPayload::default()).compute_nonce();
+    let max = testcase.epk_expiry_horizon_secs - 1;
+    let testcase = with_issuer_policy(testcase, |provider| {
+        provider.max_exp_horizon_secs = Some(max);
+    });
+
+    assert_eq!(
+        validate_error_code(testcase).await,
+        ErrorCode::IssuerPolicyViolation
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_enforces_skip_aud_checks_permission() {
+    let testcase = ProofTestCase {
+        skip_aud_checks: true,
+        ..ProofTestCase::default_with_payload(TestJWTPayload::default())
+    }
+    .compute_nonce();
+    let testcase = with_issuer_policy(testcase, |provider| {
+        provider.allow_skip_aud_checks = false;
+    });
+
+    assert_eq!(
+        validate_error_code(testcase).await,
+        ErrorCode::IssuerPolicyViolation
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_only_tolerates_iat_in_the_future_if_configured() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let jwt_payload = TestJWTPayload {
+        iat: now + 30,
+        ..TestJWTPayload::default()
+    };
+    let testcase = ProofTestCase::default_with_payload(jwt_payload).compute_nonce();
+    assert_eq!(
+        validate_error_code(testcase.clone()).await,
+        ErrorCode::JwtIssuedInFuture
+    );
+
+    let tolerated = with_issuer_policy(testcase, |provider| {
+        provider.iat_leeway_secs = 60;
+    });
+    let (state, jwk_keypair, _) = init_test_state(&tolerated);
+    let request = tolerated.convert_to_prover_request(&jwk_keypair);
+    assert!(
+        validate_handler(State(state), WithRejection(Json(request), PhantomData))
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_can_require_email_verified_for_any_uid_key() {
+    let testcase = ProofTestCase {
+        uid_key: String::from("sub"),
+        ..ProofTestCase::default_with_payload(TestJWTPayload {
+            email_verified: Some(false),
+            ..TestJWTPayload::default()
+        })
+    }
+    .compute_nonce();
+    let testcase = with_issuer_policy(testcase, |provider| {
+        provider.require_email_verified = true;
+    });
+
+    assert_eq!(
+        validate_error_code(testcase).await,
+        ErrorCode::EmailNotVerified
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn validate_rejects_aud_arrays() {
+    let mut payload = serde_json::to_value(TestJWTPayload::default()).unwrap();
+    let aud = payload["aud"].clone();
+    payload["aud"] = json!([aud, "some-other-app"]);
+    let testcase = ProofTestCase::default_with_payload(payload).compute_nonce();
+
+    // One of the audiences is allowed, but the circuit can't parse the array.
+    let mut allowed = testcase.clone();
+    for provider in &mut allowed.prover_service_config.oidc_providers {
+        provider.allowed_auds = vec![String::from("some-other-app")];
+    }
+    assert_eq!(
+        validate_error_code(allowed).await,
+        ErrorCode::JwtAudUnsupported
+    );
+
+    let mut not_allowed = testcase;
+    for provider in &mut not_allowed.prover_service_config.oidc_providers {
+        provider.allowed_auds = vec![String::from("yet-another-app")];
+    }
+    assert_eq!(
+        validate_error_code(not_allowed).await,
+        ErrorCode::IssuerPolicyViolation
+    );
+}
@@ -0,0 +1,145 @@
+// Copyright © Aptos Foundation
+
+use crate::{config::TlsConfig, watcher};
+use anyhow::{anyhow, Context, Result};
+use axum::{middleware::AddExtension, Extension};
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use futures::future::BoxFuture;
+use rustls::{
+    server::AllowAnyAnonymousOrAuthenticatedClient, Certificate, PrivateKey, RootCertStore,
+};
+use std::{fs::File, io, io::BufReader, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    task::JoinHandle,
+};
+use tower::Layer;
+use tracing::{error, info};
+
+/// Whether the client of a request served over TLS presented a certificate. If it did, the
+/// certificate chains up to one of the CAs in `tls.client_ca_path`, or the handshake would have
+/// failed.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientCertificate {
+    pub verified: bool,
+}
+
+/// Terminates TLS like [`RustlsAcceptor`], and adds a [`ClientCertificate`] extension to every
+/// request on the connection, so that only the routes that need one have to require it.
+#[derive(Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    pub fn new(rustls_config: RustlsConfig) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(rustls_config),
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = <RustlsAcceptor as Accept<I, S>>::Stream;
+    type Service = AddExtension<S, ClientCertificate>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let accept = self.inner.accept(stream, service);
+        Box::pin(async move {
+            let (stream, service) = accept.await?;
+            let verified = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .is_some_and(|certs| !certs.is_empty());
+            let service = Extension(ClientCertificate { verified }).layer(service);
+            Ok((stream, service))
+        })
+    }
+}
+
+/// Build the rustls server config for `tls`. If `tls.client_ca_path` is set, client certificates
+/// are requested and must chain up to one of the CAs in it, but clients may still connect without
+/// one: see [`ClientCertificate`]. Offers HTTP/2 as well as HTTP/1.1.
+pub fn rustls_server_config(tls: &TlsConfig) -> Result<rustls::ServerConfig> {
+    let cert_chain = load_certs(&tls.cert_path)?;
+    let key = load_private_key(&tls.key_path)?;
+
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = match &tls.client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(client_ca_path)? {
+                roots
+                    .add(&cert)
+                    .with_context(|| format!("invalid CA certificate in {client_ca_path}"))?;
+            }
+            builder.with_client_cert_verifier(
+                AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+            )
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(cert_chain, key)
+        .context("invalid TLS certificate or key")?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Reload the certificate, key and client CAs into `rustls_config` whenever one of their files
+/// changes. If the new files do not load, keeps serving with the old ones.
+pub fn spawn_reload_loop(tls: TlsConfig, rustls_config: RustlsConfig) -> JoinHandle<()> {
+    let paths = [
+        Some(&tls.cert_path),
+        Some(&tls.key_path),
+        tls.client_ca_path.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(PathBuf::from)
+    .collect();
+    let poll_interval = Duration::from_secs(tls.reload_poll_secs.max(1));
+
+    watcher::spawn_watch_loop(paths, poll_interval, move || {
+        match rustls_server_config(&tls) {
+            Ok(config) => {
+                rustls_config.reload_from_config(Arc::new(config));
+                info!("Reloaded TLS certificate");
+            }
+            Err(e) => error!(
+                error = format!("{e:#}"),
+                "Failed to reload TLS certificate; still using the old one"
+            ),
+        }
+    })
+}
+
+fn load_certs(path: &str) -> Result<Vec<Certificate>> {
+    let file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map(|cert| cert.map(|cert| Certificate(cert.to_vec())))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to parse certificates in {path}"))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates in {path}"));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKey> {
+    let file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("failed to parse private key in {path}"))?
+        .ok_or_else(|| anyhow!("no private key in {path}"))?;
+    Ok(PrivateKey(key.secret_der().to_vec()))
+}
@@ -1,20 +1,26 @@
 mod sign;
+pub mod signer;
 pub mod verification_logic;
 
-use crate::api::RequestInput;
+use crate::api::{ErrorCode, RequestInput};
 use crate::config::ProverServiceConfig;
+use crate::error::{CodedError, WithErrorCode};
 use crate::input_processing::types::VerifiedInput;
 use crate::jwk_fetching;
 use crate::jwk_fetching::get_federated_jwk;
 use crate::state::ProverServiceState;
-use crate::training_wheels::verification_logic::compute_nonce;
-use anyhow::{anyhow, bail, ensure};
-use aptos_keyless_common::input_processing::encoding::{AsFr, DecodedJWT};
+use crate::training_wheels::verification_logic::{
+    check_jwt_alg_matches_jwk, check_jwt_header, circuit_supports, compute_nonce,
+};
+use anyhow::{bail, ensure};
+use aptos_keyless_common::input_processing::config::CircuitConfig;
+use aptos_keyless_common::input_processing::encoding::{AsFr, DecodedJWT, FromB64, JwtParts};
 use aptos_keyless_common::logging;
 use aptos_keyless_common::logging::HasLoggableError;
 use aptos_types::jwks::rsa::RSA_JWK;
 pub use sign::sign;
 pub use sign::verify;
+pub use sign::{Groth16ProofAndStatementWithVk, TrainingWheelsMessage};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 pub use verification_logic::validate_jwt_sig;
@@ -23,16 +29,74 @@ pub use verification_logic::validate_jwt_sig;
 /// If a request passes this, we should be convinced that this is the *public statement* to be proved is correct.
 pub async fn preprocess_and_validate_request(
     prover: &ProverServiceState,
+    circuit_config: &CircuitConfig,
     req: &RequestInput,
 ) -> anyhow::Result<VerifiedInput> {
     let _span = logging::new_span("TrainingWheelChecks");
-    let jwt = DecodedJWT::from_b64(&req.jwt_b64).log_err()?;
-    let jwk = get_jwk(&prover.config, &jwt).await.log_err()?;
+    let config = prover.config();
+    check_jwt_header(&req.jwt_b64).log_err()?;
+    let jwt = DecodedJWT::from_b64(&req.jwt_b64)
+        .with_error_code(ErrorCode::JwtMalformed)
+        .log_err()?;
+    let jwk = get_jwk(&config, &jwt)
+        .await
+        .with_error_code(ErrorCode::JwkNotFound)
+        .log_err()?;
+    check_jwt_alg_matches_jwk(&jwt.header, &jwk).log_err()?;
+    let policy = config.issuer_policy(&jwt.payload.iss);
 
     {
         // Keyless relation condition 10 captured: https://github.com/aptos-foundation/AIPs/blob/f133e29d999adf31c4f41ce36ae1a808339af71e/aips/aip-108.md?plain=1#L95
         let _span = logging::new_span("VerifyJWTSignature");
-        validate_jwt_sig(jwk.as_ref(), &req.jwt_b64, &prover.config).log_err()?;
+        let alg = validate_jwt_sig(jwk.as_ref(), &req.jwt_b64, &config, &policy).log_err()?;
+        ensure!(
+            circuit_supports(alg, &jwk),
+            CodedError::msg(
+                ErrorCode::JwtAlgorithmUnsupported,
+                format!("the circuit cannot prove {alg:?} signatures under this JWK")
+            )
+        );
+    }
+
+    {
+        let _span = logging::new_span("CheckIssuerPolicy");
+        ensure!(
+            policy.allowed_auds.is_empty()
+                || policy
+                    .allowed_auds
+                    .iter()
+                    .any(|aud| jwt.payload.aud.contains(aud)),
+            CodedError::msg(
+                ErrorCode::IssuerPolicyViolation,
+                format!(
+                    "aud not allowed for {}: {}",
+                    jwt.payload.iss, jwt.payload.aud
+                )
+            )
+        );
+        ensure!(
+            req.exp_horizon_secs <= policy.max_exp_horizon_secs.unwrap_or(u64::MAX),
+            CodedError::msg(
+                ErrorCode::IssuerPolicyViolation,
+                format!("exp_horizon_secs is too large for {}", jwt.payload.iss)
+            )
+        );
+        ensure!(
+            !req.skip_aud_checks || policy.allow_skip_aud_checks,
+            CodedError::msg(
+                ErrorCode::IssuerPolicyViolation,
+                format!("skip_aud_checks is not allowed for {}", jwt.payload.iss)
+            )
+        );
+        // The circuit parses `aud` as a string, so a token with several audiences cannot be
+        // proved even if one of them is allowed.
+        ensure!(
+            jwt.payload.aud.as_single().is_some(),
+            CodedError::msg(
+                ErrorCode::JwtAudUnsupported,
+                format!("aud arrays are not supported: {}", jwt.payload.aud)
+            )
+        );
     }
 
     {
@@ -40,17 +104,24 @@ pub async fn preprocess_and_validate_request(
         let _span = logging::new_span("EnsureEpkNotExpired");
         ensure!(
             (req.exp_date_secs as u128)
-                < (jwt.payload.iat as u128) + (req.exp_horizon_secs as u128)
+                < (jwt.payload.iat as u128) + (req.exp_horizon_secs as u128),
+            CodedError::msg(
+                ErrorCode::EpkExpired,
+                "exp_date_secs is not before the JWT's iat plus exp_horizon_secs"
+            )
         );
     }
 
-    if prover.config.enable_jwt_iat_not_in_future_check {
+    if config.enable_jwt_iat_not_in_future_check {
         //TODO: should it be always enabled?
         let _span = logging::new_span("CheckIatNotInFuture");
         let now_unix_secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
         ensure!(
-            jwt.payload.iat <= now_unix_secs,
-            "jwt which was issued in the future"
+            jwt.payload.iat <= now_unix_secs + policy.iat_leeway_secs,
+            CodedError::msg(
+                ErrorCode::JwtIssuedInFuture,
+                "jwt which was issued in the future"
+            )
         );
     }
 
@@ -61,33 +132,64 @@ pub async fn preprocess_and_validate_request(
             req.exp_date_secs,
             &req.epk,
             req.epk_blinder.as_fr(),
-            prover.circuit_config(),
+            circuit_config,
         )
         .log_err()?;
-        ensure!(jwt.payload.nonce == computed_nonce.to_string());
+        ensure!(
+            jwt.payload.nonce == computed_nonce.to_string(),
+            CodedError::msg(
+                ErrorCode::NonceMismatch,
+                "JWT nonce does not match the one computed from the EPK, its expiry date and blinder"
+            )
+        );
     }
 
     let uid_val = {
         let _span = logging::new_span("EnsureUidKeyNotNull");
-        match req.uid_key.as_str() {
-            "email" => {
-                // Keyless relation condition 3 captured: https://github.com/aptos-foundation/AIPs/blob/f133e29d999adf31c4f41ce36ae1a808339af71e/aips/aip-108.md?plain=1#L74
-                ensure!(Some(true) == jwt.payload.email_verified);
-                jwt.payload
-                    .email
-                    .clone()
-                    .ok_or_else(|| anyhow!("Missing email in jwt payload"))?
-            }
-            "sub" => jwt
-                .payload
-                .sub
-                .clone()
-                .ok_or_else(|| anyhow!("Missing sub in jwt payload"))?,
-            _ => bail!("Unrecognized uid_key: {}", req.uid_key),
+        ensure!(
+            policy.uid_keys.contains(&req.uid_key),
+            CodedError::msg(
+                ErrorCode::UidKeyUnsupported,
+                format!(
+                    "Unsupported uid_key for {}: {}",
+                    jwt.payload.iss, req.uid_key
+                )
+            )
+        );
+        if req.uid_key == "email" || policy.require_email_verified {
+            // Keyless relation condition 3 captured: https://github.com/aptos-foundation/AIPs/blob/f133e29d999adf31c4f41ce36ae1a808339af71e/aips/aip-108.md?plain=1#L74
+            ensure!(
+                Some(true) == jwt.payload.email_verified,
+                CodedError::msg(ErrorCode::EmailNotVerified, "email is not verified")
+            );
         }
+        uid_claim(&req.jwt_b64, &req.uid_key)?
     };
 
-    VerifiedInput::new(req, jwk, jwt, uid_val).log_err()
+    VerifiedInput::new(req, jwk, jwt, uid_val)
+        .with_error_code(ErrorCode::InvalidRequest)
+        .log_err()
+}
+
+/// The value of the JWT claim named `uid_key`, which must be a string.
+fn uid_claim(jwt_b64: &str, uid_key: &str) -> anyhow::Result<String> {
+    let payload: serde_json::Value = serde_json::from_str(
+        &JwtParts::from_b64(jwt_b64)
+            .and_then(|jwt_parts| jwt_parts.payload_decoded())
+            .with_error_code(ErrorCode::JwtMalformed)?,
+    )
+    .with_error_code(ErrorCode::JwtMalformed)?;
+    match payload.get(uid_key) {
+        Some(serde_json::Value::String(uid_val)) => Ok(uid_val.clone()),
+        Some(_) => bail!(CodedError::msg(
+            ErrorCode::UidClaimMissing,
+            format!("{uid_key} in jwt payload is not a string")
+        )),
+        None => bail!(CodedError::msg(
+            ErrorCode::UidClaimMissing,
+            format!("Missing {uid_key} in jwt payload")
+        )),
+    }
 }
 
 /// This function returns the same JWK that the Aptos validators would expect for this JWT.
@@ -104,5 +206,5 @@ async fn get_jwk(
         return default_jwk;
     }
     ensure!(prover_config.enable_federated_jwks);
-    get_federated_jwk(jwt).await
+    get_federated_jwk(&prover_config.federated_issuer_patterns, jwt).await
 }
@@ -1,32 +1,95 @@
 use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature},
-    CryptoMaterialError, SigningKey,
+    traits::signing_message,
+    CryptoMaterialError, HashValue, Signature, SigningKey,
 };
+use aptos_crypto_derive::{BCSCryptoHash, CryptoHasher};
 use aptos_keyless_common::PoseidonHash;
 use aptos_types::{
     keyless::{Groth16Proof, Groth16ProofAndStatement},
     transaction::authenticator::{EphemeralPublicKey, EphemeralSignature},
 };
+use serde::{Deserialize, Serialize};
 
 use crate::api::ProverServiceResponse;
+use crate::config::TwSigningMode;
+
+/// What the training wheels signature covers in [`TwSigningMode::V1`]: the proof and its
+/// statement, like [`Groth16ProofAndStatement`], and the VK that the proof verifies under.
+///
+/// The signed message is the BCS encoding of this struct, prefixed with the domain separator
+/// `SHA3-256("APTOS::Groth16ProofAndStatementWithVk")`.
+#[derive(Debug, Clone, Serialize, Deserialize, CryptoHasher, BCSCryptoHash)]
+pub struct Groth16ProofAndStatementWithVk {
+    pub proof: Groth16Proof,
+    pub public_inputs_hash: PoseidonHash,
+    /// See [`crate::groth16_vk::OnChainGroth16VerificationKey::hash`].
+    pub vk_hash: [u8; 32],
+}
+
+/// The message that the training wheels key signs, in one of the [`TwSigningMode`]s.
+#[derive(Debug, Clone)]
+pub enum TrainingWheelsMessage {
+    V0(Groth16ProofAndStatement),
+    V1(Groth16ProofAndStatementWithVk),
+}
+
+impl TrainingWheelsMessage {
+    pub fn new(
+        mode: TwSigningMode,
+        proof: Groth16Proof,
+        public_inputs_hash: PoseidonHash,
+        vk_hash: &HashValue,
+    ) -> Self {
+        match mode {
+            TwSigningMode::V0 => Self::V0(Groth16ProofAndStatement {
+                proof,
+                public_inputs_hash,
+            }),
+            TwSigningMode::V1 => Self::V1(Groth16ProofAndStatementWithVk {
+                proof,
+                public_inputs_hash,
+                vk_hash: vk_hash.to_vec().try_into().unwrap(),
+            }),
+        }
+    }
+
+    /// The bytes that the signature is computed over.
+    pub fn signing_message(&self) -> Result<Vec<u8>, CryptoMaterialError> {
+        match self {
+            Self::V0(message) => signing_message(message),
+            Self::V1(message) => signing_message(message),
+        }
+    }
+
+    pub fn verify(
+        &self,
+        signature: &Ed25519Signature,
+        pub_key: &Ed25519PublicKey,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Self::V0(message) => signature.verify(message, pub_key),
+            Self::V1(message) => signature.verify(message, pub_key),
+        }
+    }
+}
 
 pub fn sign(
     private_key: &Ed25519PrivateKey,
-    proof: Groth16Proof,
-    public_inputs_hash: PoseidonHash,
+    message: &TrainingWheelsMessage,
 ) -> Result<Ed25519Signature, CryptoMaterialError> {
-    let message_to_sign: Groth16ProofAndStatement = Groth16ProofAndStatement {
-        proof,
-        public_inputs_hash,
-    };
-
-    private_key.sign(&message_to_sign)
+    match message {
+        TrainingWheelsMessage::V0(message) => private_key.sign(message),
+        TrainingWheelsMessage::V1(message) => private_key.sign(message),
+    }
 }
 
 // For debugging.
 pub fn verify(
     response: &ProverServiceResponse,
     pub_key: &Ed25519PublicKey,
+    mode: TwSigningMode,
+    vk_hash: &HashValue,
 ) -> Result<(), anyhow::Error> {
     match response {
         ProverServiceResponse::Error { .. } => {
@@ -38,13 +101,11 @@ pub fn verify(
             training_wheels_signature,
         } => {
             let ephem_tw_sig = EphemeralSignature::try_from(training_wheels_signature.as_slice())?;
-            ephem_tw_sig.verify(
-                &Groth16ProofAndStatement {
-                    proof: *proof,
-                    public_inputs_hash: *public_inputs_hash,
-                },
-                &EphemeralPublicKey::ed25519(pub_key.clone()),
-            )
+            let ephem_tw_pk = EphemeralPublicKey::ed25519(pub_key.clone());
+            match TrainingWheelsMessage::new(mode, *proof, *public_inputs_hash, vk_hash) {
+                TrainingWheelsMessage::V0(message) => ephem_tw_sig.verify(&message, &ephem_tw_pk),
+                TrainingWheelsMessage::V1(message) => ephem_tw_sig.verify(&message, &ephem_tw_pk),
+            }
         }
     }
 }
@@ -0,0 +1,256 @@
+// Copyright © Aptos Foundation
+
+//! Backends that hold a training wheels key and sign with it.
+//!
+//! Besides keys in process memory, a key can be kept in a passphrase-encrypted file, or behind a
+//! remote signer, so that it never shows up in the prover's environment.
+//!
+//! A remote signer is an HTTP endpoint that takes `POST` requests with a JSON body of the form
+//! `{"public_key": "0x..", "message": "<hex>"}` and responds with `{"signature": "<hex>"}`: the
+//! Ed25519 signature of the `message` bytes under the key with that public key. If the signer is
+//! configured with an auth token, requests carry it as `Authorization: Bearer <token>`.
+
+use super::TrainingWheelsMessage;
+use crate::config::TwSignerConfig;
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng},
+    AeadCore, Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use aptos_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature},
+    ValidCryptoMaterialStringExt,
+};
+use argon2::Argon2;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, sync::Arc, time::Duration};
+
+/// Signs proofs with a training wheels key.
+#[async_trait]
+pub trait TrainingWheelsSigner: fmt::Debug + Send + Sync {
+    fn public_key(&self) -> &Ed25519PublicKey;
+
+    async fn sign(&self, message: &TrainingWheelsMessage) -> Result<Ed25519Signature>;
+}
+
+/// Build the signer that `config` describes.
+pub fn from_config(config: &TwSignerConfig) -> Result<Arc<dyn TrainingWheelsSigner>> {
+    Ok(match config {
+        TwSignerConfig::EncryptedFile {
+            path,
+            passphrase_path,
+        } => {
+            let passphrase = fs::read_to_string(passphrase_path)
+                .with_context(|| format!("Couldn't read {passphrase_path}"))?;
+            let encrypted_key: EncryptedKeyFile = serde_json::from_str(
+                &fs::read_to_string(path).with_context(|| format!("Couldn't read {path}"))?,
+            )
+            .with_context(|| format!("{path} is not an encrypted key file"))?;
+            Arc::new(InMemorySigner::new(
+                encrypted_key.decrypt(passphrase.trim_end())?,
+            ))
+        }
+        TwSignerConfig::Remote {
+            url,
+            public_key,
+            auth_token_path,
+            timeout_secs,
+        } => {
+            let auth_token = auth_token_path
+                .as_ref()
+                .map(|path| {
+                    fs::read_to_string(path)
+                        .map(|token| String::from(token.trim_end()))
+                        .with_context(|| format!("Couldn't read {path}"))
+                })
+                .transpose()?;
+            Arc::new(RemoteSigner::new(
+                url,
+                Ed25519PublicKey::from_encoded_string(public_key)?,
+                auth_token,
+                Duration::from_secs(*timeout_secs),
+            )?)
+        }
+    })
+}
+
+/// A key in process memory.
+#[derive(Debug)]
+pub struct InMemorySigner {
+    private_key: Ed25519PrivateKey,
+    public_key: Ed25519PublicKey,
+}
+
+impl InMemorySigner {
+    pub fn new(private_key: Ed25519PrivateKey) -> Self {
+        Self {
+            public_key: Ed25519PublicKey::from(&private_key),
+            private_key,
+        }
+    }
+}
+
+#[async_trait]
+impl TrainingWheelsSigner for InMemorySigner {
+    fn public_key(&self) -> &Ed25519PublicKey {
+        &self.public_key
+    }
+
+    async fn sign(&self, message: &TrainingWheelsMessage) -> Result<Ed25519Signature> {
+        Ok(super::sign(&self.private_key, message)?)
+    }
+}
+
+#[derive(Serialize)]
+struct RemoteSignRequest<'a> {
+    public_key: &'a str,
+    #[serde(with = "hex")]
+    message: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct RemoteSignResponse {
+    #[serde(with = "hex")]
+    signature: Vec<u8>,
+}
+
+/// A key held by a remote signer; see the module docs for the protocol. Signatures are checked
+/// against the configured public key before they are used.
+pub struct RemoteSigner {
+    url: String,
+    public_key: Ed25519PublicKey,
+    auth_token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    pub fn new(
+        url: &str,
+        public_key: Ed25519PublicKey,
+        auth_token: Option<String>,
+        timeout: Duration,
+    ) -> Result<Self> {
+        Ok(Self {
+            url: String::from(url),
+            public_key,
+            auth_token,
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+        })
+    }
+}
+
+impl fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteSigner")
+            .field("url", &self.url)
+            .field("public_key", &self.public_key)
+            .field(
+                "auth_token",
+                &self.auth_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+#[async_trait]
+impl TrainingWheelsSigner for RemoteSigner {
+    fn public_key(&self) -> &Ed25519PublicKey {
+        &self.public_key
+    }
+
+    async fn sign(&self, message: &TrainingWheelsMessage) -> Result<Ed25519Signature> {
+        let mut request = self.client.post(&self.url).json(&RemoteSignRequest {
+            public_key: &self.public_key.to_encoded_string()?,
+            message: message.signing_message()?,
+        });
+        if let Some(auth_token) = &self.auth_token {
+            request = request.bearer_auth(auth_token);
+        }
+
+        let response: RemoteSignResponse = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context("remote signer request failed")?
+            .json()
+            .await
+            .context("remote signer sent a malformed response")?;
+        let signature = Ed25519Signature::try_from(response.signature.as_slice())?;
+        message
+            .verify(&signature, &self.public_key)
+            .context("remote signer's signature does not verify under its public key")?;
+        Ok(signature)
+    }
+}
+
+/// A training wheels private key, encrypted with AES-256-GCM under a key derived from a
+/// passphrase with Argon2id. Stored as JSON.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedKeyFile {
+    #[serde(with = "hex")]
+    pub salt: Vec<u8>,
+    #[serde(with = "hex")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "hex")]
+    pub ciphertext: Vec<u8>,
+}
+
+impl EncryptedKeyFile {
+    pub fn encrypt(private_key: &Ed25519PrivateKey, passphrase: &str) -> Result<Self> {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher(passphrase, &salt)?
+            .encrypt(&nonce, private_key.to_bytes().as_slice())
+            .map_err(|e| anyhow!("couldn't encrypt the key: {e}"))?;
+        Ok(Self {
+            salt,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> Result<Ed25519PrivateKey> {
+        ensure!(self.nonce.len() == 12, "nonce must be 12 bytes long");
+        let plaintext = cipher(passphrase, &self.salt)?
+            .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| anyhow!("couldn't decrypt the key; is the passphrase right?"))?;
+        Ok(Ed25519PrivateKey::try_from(plaintext.as_slice())?)
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<Aes256Gcm> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("couldn't derive the key from the passphrase: {e}"))?;
+    Aes256Gcm::new_from_slice(&key).map_err(|e| anyhow!("invalid key length: {e}"))
+}
+
+/// This is not a UT, but a tool to encrypt a training wheels private key for
+/// [`TwSignerConfig::EncryptedFile`].
+#[test]
+fn tw_sk_encrypter() {
+    use std::io::Write;
+
+    if let (Ok(path_in), Ok(passphrase_path), Ok(path_out)) = (
+        std::env::var("LOCAL_TW_SK_IN"),
+        std::env::var("TW_SK_PASSPHRASE_IN"),
+        std::env::var("ENCRYPTED_TW_SK_OUT"),
+    ) {
+        let sk =
+            Ed25519PrivateKey::from_encoded_string(fs::read_to_string(path_in).unwrap().trim_end())
+                .unwrap();
+        let passphrase = fs::read_to_string(passphrase_path).unwrap();
+        let encrypted_key = EncryptedKeyFile::encrypt(&sk, passphrase.trim_end()).unwrap();
+        fs::File::create(path_out)
+            .unwrap()
+            .write_all(
+                serde_json::to_string_pretty(&encrypted_key)
+                    .unwrap()
+                    .as_bytes(),
+            )
+            .unwrap();
+    }
+}
@@ -1,24 +1,107 @@
 use aptos_crypto::poseidon_bn254;
-use aptos_keyless_common::input_processing::config::CircuitConfig;
-use aptos_types::{
-    jwks::rsa::RSA_JWK, keyless::Claims, transaction::authenticator::EphemeralPublicKey,
-};
+use aptos_keyless_common::input_processing::{config::CircuitConfig, encoding::JwtHeader};
+use aptos_types::{jwks::rsa::RSA_JWK, transaction::authenticator::EphemeralPublicKey};
 use ark_bn254::Fr;
-use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, Validation};
 
-use crate::config::ProverServiceConfig;
-use anyhow::Result;
+use crate::api::ErrorCode;
+use crate::config::{IssuerPolicy, ProverServiceConfig};
+use crate::error::CodedError;
+use anyhow::{bail, ensure, Result};
 
-pub fn validate_jwt_sig(jwk: &RSA_JWK, jwt: &str, config: &ProverServiceConfig) -> Result<()> {
-    let mut validation = Validation::new(Algorithm::RS256);
+/// Reject JWTs whose header shows that they are not signed ID tokens that we can verify: JWEs,
+/// unsigned JWTs, JWTs of another `typ` (e.g., `at+jwt` access tokens), and JWTs with `crit`
+/// params, none of which we understand.
+pub fn check_jwt_header(jwt: &str) -> Result<JwtHeader> {
+    let unsupported = |msg: &'static str| CodedError::msg(ErrorCode::JwtHeaderUnsupported, msg);
+    let segments: Vec<&str> = jwt.split('.').collect();
+    if segments.len() == 5 {
+        bail!(unsupported("JWEs are not supported"));
+    }
+    let header = JwtHeader::from_b64url(segments[0])
+        .map_err(|e| CodedError::new(ErrorCode::JwtMalformed, e))?;
+
+    ensure!(header.enc.is_none(), unsupported("JWEs are not supported"));
+    ensure!(
+        !header.alg.is_empty() && !header.alg.eq_ignore_ascii_case("none"),
+        unsupported("unsigned JWTs are not supported")
+    );
+    ensure!(
+        segments
+            .get(2)
+            .is_some_and(|signature| !signature.is_empty()),
+        unsupported("unsigned JWTs are not supported")
+    );
+    ensure!(!header.kid.is_empty(), unsupported("JWT header has no kid"));
+    ensure!(
+        header.typ.iter().all(|typ| typ.eq_ignore_ascii_case("JWT")),
+        unsupported("JWT typ must be JWT")
+    );
+    ensure!(
+        header.crit.iter().all(Vec::is_empty),
+        unsupported("JWT header has unsupported crit params")
+    );
+    Ok(header)
+}
+
+/// Reject JWTs whose `alg` is not the one that their JWK, if it has one, is for.
+pub fn check_jwt_alg_matches_jwk(header: &JwtHeader, jwk: &RSA_JWK) -> Result<()> {
+    ensure!(
+        jwk.alg.is_empty() || jwk.alg == header.alg,
+        CodedError::msg(
+            ErrorCode::JwtHeaderUnsupported,
+            format!(
+                "JWT alg {} does not match its JWK's alg {}",
+                header.alg, jwk.alg
+            )
+        )
+    );
+    Ok(())
+}
+
+/// Check the JWT's signature, under the algorithm its header declares, and its `exp`. Returns
+/// that algorithm, which must be one of `config.allowed_jwt_algorithms`.
+pub fn validate_jwt_sig(
+    jwk: &RSA_JWK,
+    jwt: &str,
+    config: &ProverServiceConfig,
+    policy: &IssuerPolicy,
+) -> Result<Algorithm> {
+    let alg = jsonwebtoken::decode_header(jwt)
+        .map_err(|e| CodedError::new(ErrorCode::JwtMalformed, e))?
+        .alg;
+    ensure!(
+        config.allowed_jwt_algorithms.contains(&alg),
+        CodedError::msg(
+            ErrorCode::JwtAlgorithmUnsupported,
+            format!("JWT alg {alg:?} is not allowed")
+        )
+    );
+    let mut validation = Validation::new(alg);
+    validation.leeway = policy.exp_leeway_secs;
     if !config.enable_jwt_exp_not_in_the_past_check {
         //TODO: should it be always enabled?
         validation.validate_exp = false;
     }
     let key = &DecodingKey::from_rsa_components(&jwk.n, &jwk.e)?;
 
-    let _claims = jsonwebtoken::decode::<Claims>(jwt, key, &validation)?;
-    Ok(())
+    // The claims are decoded as plain JSON: `Claims` wants a string `aud`, and arrays are
+    // rejected later with a clearer error.
+    let _claims =
+        jsonwebtoken::decode::<serde_json::Value>(jwt, key, &validation).map_err(|e| {
+            let code = match e.kind() {
+                ErrorKind::ExpiredSignature => ErrorCode::JwtExpired,
+                _ => ErrorCode::JwtSignatureInvalid,
+            };
+            CodedError::new(code, e)
+        })?;
+    Ok(alg)
+}
+
+/// Whether the circuit can prove a signature of algorithm `alg` under `jwk`: it only implements
+/// RSA PKCS#1 v1.5 with SHA-256, for the public exponent 65537.
+pub fn circuit_supports(alg: Algorithm, jwk: &RSA_JWK) -> bool {
+    alg == Algorithm::RS256 && jwk.e == "AQAB"
 }
 
 pub fn compute_nonce(
@@ -1 +1,55 @@
+// Copyright © Aptos Foundation
 
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::task::JoinHandle;
+
+/// Detects modifications of a set of files by polling their modification times.
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    last_modified: Vec<Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let last_modified = paths.iter().map(|path| modified_time(path)).collect();
+        Self {
+            paths,
+            last_modified,
+        }
+    }
+
+    /// Whether any of the files was modified, created or removed since the last call (or since
+    /// the watcher was created).
+    pub fn poll_changed(&mut self) -> bool {
+        let last_modified: Vec<_> = self.paths.iter().map(|path| modified_time(path)).collect();
+        let changed = last_modified != self.last_modified;
+        self.last_modified = last_modified;
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Calls `on_change` every time one of `paths` changes, checking every `poll_interval`.
+pub fn spawn_watch_loop(
+    paths: Vec<PathBuf>,
+    poll_interval: Duration,
+    mut on_change: impl FnMut() + Send + 'static,
+) -> JoinHandle<()> {
+    let mut watcher = FileWatcher::new(paths);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(poll_interval);
+        loop {
+            interval.tick().await;
+            if watcher.poll_changed() {
+                on_change();
+            }
+        }
+    })
+}
@@ -18,7 +18,9 @@ impl PathStr for NamedTempFile {
     }
 }
 
-pub fn witness_gen(
+/// Runs the witness generator on the blocking thread pool, so that it does not stall the runtime's
+/// worker threads.
+pub async fn witness_gen(
     config: &ProverServiceConfig,
     circuit_input_signals: &CircuitInputSignals<Padded>,
 ) -> Result<NamedTempFile> {
@@ -32,13 +34,26 @@ pub fn witness_gen(
         fs::write("formatted_input.json", &formatted_input_str).unwrap();
     }
 
-    let input_file = NamedTempFile::new()?;
-    let witness_file = NamedTempFile::new()?;
+    let config = config.clone();
+    tokio::task::spawn_blocking(move || {
+        let input_file = NamedTempFile::new()?;
+
+        fs::write(input_file.path(), formatted_input_str.as_bytes())?;
+
+        witness_gen_from_file(&config, input_file.path_str()?)
+    })
+    .await?
+}
 
-    fs::write(input_file.path(), formatted_input_str.as_bytes())?;
+/// Run the witness generator of the setup in `config` on circuit input that is already in a file,
+/// e.g., the setup self-test's input.
+pub fn witness_gen_from_file(
+    config: &ProverServiceConfig,
+    input_file_path: &str,
+) -> Result<NamedTempFile> {
+    let witness_file = NamedTempFile::new()?;
 
-    let output =
-        get_witness_command(config, input_file.path_str()?, witness_file.path_str()?).output()?;
+    let output = get_witness_command(config, input_file_path, witness_file.path_str()?).output()?;
 
     // Check if the command executed successfully
     if output.status.success() {
@@ -82,3 +97,15 @@ fn get_witness_command(
     c.args([input_file_path, witness_file_path]); // Example arguments
     c
 }
+
+/// The files that witness generation runs, as opposed to reads inputs from.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn witness_gen_paths(config: &ProverServiceConfig) -> Vec<String> {
+    vec![config.witness_gen_js_path(), config.witness_gen_wasm_path()]
+}
+
+/// The files that witness generation runs, as opposed to reads inputs from.
+#[cfg(target_arch = "x86_64")]
+pub fn witness_gen_paths(config: &ProverServiceConfig) -> Vec<String> {
+    vec![config.witness_gen_binary_path()]
+}